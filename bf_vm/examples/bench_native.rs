@@ -0,0 +1,43 @@
+//! Compares `Interpreter::run` with the native interpreter on a long running
+//! program: `cargo run --release --example bench_native`.
+use bf_vm::code;
+use bf_vm::interpreter::Interpreter;
+use bf_vm::native::NativeInterpreter;
+use std::time::Instant;
+
+fn main() {
+    // two nested 256 iteration loops inside a 4 iteration loop, ~1.3M cycles
+    let source = b"++++[>-[>-[>+<-]<-]<-]>>>.".to_vec();
+
+    let start = Instant::now();
+    let mut native = NativeInterpreter::<u8>::new(code::compile(source.clone()));
+    let output = native.run();
+    let native_time = start.elapsed();
+    println!(
+        "native run:       {:>10?} ({} cycles, output {:?})",
+        native_time, native.cycles, output
+    );
+
+    let start = Instant::now();
+    let (_, matrix) = native.trace();
+    let trace_time = start.elapsed();
+    println!(
+        "native trace:     {:>10?} ({} processor rows)",
+        trace_time,
+        matrix.processor_matrix.len()
+    );
+
+    let start = Instant::now();
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(source));
+    vm.run();
+    let field_time = start.elapsed();
+    println!("field run:        {:>10?}", field_time);
+
+    assert_eq!(vm.matrix, matrix);
+    println!(
+        "speedup: {:.1}x plain execution, {:.1}x trace generation",
+        field_time.as_secs_f64() / native_time.as_secs_f64(),
+        field_time.as_secs_f64() / trace_time.as_secs_f64()
+    );
+}
@@ -0,0 +1,306 @@
+//! Static checks of a program before running or proving it. The analyzer
+//! walks the compiled program once, tracking what is known about the memory
+//! pointer and the cells from a zero tape. Whatever a loop may change is
+//! forgotten when it is entered, so the analysis never claims more than holds
+//! on every iteration.
+use crate::code::{self, CompileError, Instruction};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lint {
+    /// The program does not compile, see [`code::unmatched_brackets`].
+    Compile(CompileError),
+    /// A loop reached when its cell is always 0, its body never runs.
+    DeadLoop,
+    /// `[]` reached with a nonzero cell, or a cell of unknown value.
+    InfiniteLoop,
+    /// `<` on cell 0, which traps the interpreter (`Trap::TapeUnderflow`).
+    TapeUnderflow,
+}
+
+/// A problem found in the program. Errors happen on every run, warnings only
+/// on some, e.g. inside a loop that may never be entered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub lint: Lint,
+    /// Byte offsets in the source.
+    pub span: Range<usize>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+        match &self.lint {
+            Lint::Compile(e) => write!(f, "{}", e),
+            Lint::DeadLoop => write!(f, "loop never runs, its cell is always 0 here"),
+            Lint::InfiniteLoop => write!(f, "`[]` never ends once entered"),
+            Lint::TapeUnderflow => write!(f, "'<' moves left of cell 0"),
+        }
+    }
+}
+
+/// 1-based line and column of the byte at `offset` in `source`.
+pub fn line_col(source: &[u8], offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let col = offset
+        - before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1)
+        + 1;
+    (line, col)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Analysis {
+    /// Sorted by position.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Upper bound on the cells a run touches, `None` when the memory
+    /// pointer cannot be followed, e.g. past a loop that moves it.
+    pub tape_bound: Option<usize>,
+}
+
+impl Analysis {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Value {
+    Known(u64),
+    NonZero,
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+struct State {
+    /// Exact memory pointer, if known.
+    mp: Option<usize>,
+    cells: BTreeMap<usize, Value>,
+    /// Whether the cells missing from `cells` still hold 0.
+    zero: bool,
+}
+
+impl State {
+    fn value(&self) -> Value {
+        match self.mp {
+            Some(mp) => match self.cells.get(&mp) {
+                Some(v) => *v,
+                None if self.zero => Value::Known(0),
+                None => Value::Unknown,
+            },
+            None => Value::Unknown,
+        }
+    }
+
+    fn set(&mut self, value: Value) {
+        if let Some(mp) = self.mp {
+            self.cells.insert(mp, value);
+        }
+    }
+
+    // what is still known whatever number of times `body` runs
+    fn forget(&mut self, balanced: bool) {
+        self.cells.clear();
+        self.zero = false;
+        if !balanced {
+            self.mp = None;
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    code: &'a [Instruction],
+    source_map: &'a [usize],
+    bits: u64,
+    diagnostics: Vec<Diagnostic>,
+    max_mp: Option<usize>,
+}
+
+impl Analyzer<'_> {
+    fn report(&mut self, certain: bool, lint: Lint, span: Range<usize>) {
+        let severity = if certain {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        self.diagnostics.push(Diagnostic {
+            severity,
+            lint,
+            span,
+        });
+    }
+
+    // source span of the instructions start..=end
+    fn span(&self, start: usize, end: usize) -> Range<usize> {
+        self.source_map[start]..self.source_map[end] + 1
+    }
+
+    // net pointer movement of the instructions in `ips`, `None` if one of
+    // its loops moves the pointer
+    fn movement(&self, ips: Range<usize>) -> Option<i64> {
+        let mut net = 0;
+        let mut ip = ips.start;
+        while ip < ips.end {
+            let i = self.code[ip];
+            match i.opcode {
+                code::SHL => net -= 1,
+                code::SHR => net += 1,
+                code::LB => {
+                    let end = i.operand as usize - 1;
+                    if self.movement(ip + 1..end)? != 0 {
+                        return None;
+                    }
+                    ip = end;
+                }
+                _ => {}
+            }
+            ip += 1;
+        }
+        Some(net)
+    }
+
+    /// Analyzes the instructions in `ips`, which run on every run if
+    /// `certain`. Returns `false` if they never finish.
+    fn block(&mut self, ips: Range<usize>, state: &mut State, certain: bool) -> bool {
+        let mask = u64::MAX >> (64 - self.bits);
+        let mut ip = ips.start;
+        while ip < ips.end {
+            let i = self.code[ip];
+            match i.opcode {
+                code::SHL => match state.mp {
+                    Some(0) => {
+                        self.report(certain, Lint::TapeUnderflow, self.span(ip, ip));
+                        state.mp = None;
+                        self.max_mp = None;
+                    }
+                    Some(mp) => state.mp = Some(mp - 1),
+                    None => {}
+                },
+                code::SHR => {
+                    state.mp = state.mp.map(|mp| mp + 1);
+                    if let (Some(mp), Some(max)) = (state.mp, self.max_mp) {
+                        self.max_mp = Some(max.max(mp));
+                    }
+                }
+                code::ADD | code::SUB => {
+                    let value = match state.value() {
+                        Value::Known(v) if i.opcode == code::ADD => {
+                            Value::Known(v.wrapping_add(1) & mask)
+                        }
+                        Value::Known(v) => Value::Known(v.wrapping_sub(1) & mask),
+                        _ => Value::Unknown,
+                    };
+                    state.set(value);
+                }
+                code::GETCHAR => state.set(Value::Unknown),
+                code::LB => {
+                    let end = i.operand as usize - 1;
+                    let span = self.span(ip, end);
+                    let value = state.value();
+                    let entered = value != Value::Unknown;
+                    if value == Value::Known(0) {
+                        self.report(false, Lint::DeadLoop, span);
+                    } else if end == ip + 1 {
+                        self.report(certain && entered, Lint::InfiniteLoop, span);
+                        if certain && entered {
+                            return false;
+                        }
+                    } else {
+                        // the pointer is the same on every iteration of a
+                        // balanced body, otherwise only on the first one
+                        let balanced = self.movement(ip + 1..end) == Some(0);
+                        state.forget(true);
+                        if !balanced {
+                            self.max_mp = None;
+                        }
+                        state.set(Value::NonZero);
+                        if !self.block(ip + 1..end, state, certain && entered) && certain {
+                            return false;
+                        }
+                        state.forget(balanced);
+                    }
+                    state.set(Value::Known(0));
+                    ip = end;
+                }
+                _ => {}
+            }
+            ip += 1;
+        }
+        true
+    }
+}
+
+/// Checks `source` for `bits`-bit cells and a zero initial tape. Panics
+/// unless `bits` is between 1 and 64.
+pub fn analyze(source: &[u8], bits: u64) -> Analysis {
+    let mut errors = code::unmatched_brackets(source);
+    if errors.is_empty() {
+        match code::try_compile_with_source_map(source.to_vec()) {
+            Ok((code, source_map)) => return analyze_program(&code, &source_map, bits),
+            Err(e) => errors.push(e),
+        }
+    }
+    let diagnostics = errors
+        .into_iter()
+        .map(|e| {
+            let span = match e {
+                CompileError::UnmatchedOpen { pos } | CompileError::UnmatchedClose { pos } => {
+                    pos..pos + 1
+                }
+                CompileError::TooManyInstructions => 0..source.len(),
+            };
+            Diagnostic {
+                severity: Severity::Error,
+                lint: Lint::Compile(e),
+                span,
+            }
+        })
+        .collect();
+    Analysis {
+        diagnostics,
+        tape_bound: None,
+    }
+}
+
+/// Same as [`analyze`] for a program compiled with
+/// [`code::compile_with_source_map`].
+pub fn analyze_program(code: &[Instruction], source_map: &[usize], bits: u64) -> Analysis {
+    assert!((1..=64).contains(&bits), "cells of {} bits", bits);
+    let mut analyzer = Analyzer {
+        code,
+        source_map,
+        bits,
+        diagnostics: Vec::new(),
+        max_mp: Some(0),
+    };
+    let mut state = State {
+        mp: Some(0),
+        cells: BTreeMap::new(),
+        zero: true,
+    };
+    analyzer.block(0..code.len(), &mut state, true);
+    let mut diagnostics = analyzer.diagnostics;
+    diagnostics.sort_by_key(|d| d.span.start);
+    Analysis {
+        diagnostics,
+        tape_bound: analyzer.max_mp.map(|mp| mp + 1),
+    }
+}
@@ -0,0 +1,445 @@
+//! A small macro assembler that lowers to plain BF text for
+//! [`code::compile`]. On top of the eight instructions it has:
+//!
+//! - `op*N` repeats an instruction, e.g. `+*10`;
+//! - `cell name N` names tape cell `N`, and `@name` moves the pointer there
+//!   with `<`/`>`. The assembler follows the pointer through the program, so
+//!   `@name` needs every loop before it to leave the pointer where it found it;
+//! - `def name { ... }` defines a macro, expanded wherever `name` appears
+//!   after the definition;
+//! - `include "file"` assembles another file in place, once: later includes
+//!   of the same file do nothing. The path is relative to the directory of
+//!   the including file;
+//! - `#` comments to the end of the line.
+//!
+//! Every byte of the output keeps the [`Location`] it was written at, so
+//! compiled instructions map back to the assembler sources.
+use crate::code::{self, CompileError, Instruction};
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// Byte offset in one of the [`Assembly::files`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub file: usize,
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnexpectedChar(char),
+    /// A `cell` or `def` without its name or value, or an `include` without
+    /// its quoted path.
+    Expected(&'static str),
+    UnknownName(String),
+    DuplicateName(String),
+    RecursiveMacro(String),
+    IncludeNotFound(String),
+    RecursiveInclude(String),
+    /// `@name` after a loop that moves the pointer.
+    UnknownPointer(String),
+    /// `@name` inside a loop that moves the pointer, so the move is only
+    /// right on the first iteration.
+    UnbalancedLoop(String),
+    UnmatchedOpen,
+    UnmatchedClose,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub kind: AsmErrorKind,
+    pub file: String,
+    pub offset: usize,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: ", self.file, self.offset)?;
+        match &self.kind {
+            AsmErrorKind::UnexpectedChar(c) => write!(f, "unexpected {:?}", c),
+            AsmErrorKind::Expected(what) => write!(f, "expected {}", what),
+            AsmErrorKind::UnknownName(name) => write!(f, "unknown name {}", name),
+            AsmErrorKind::DuplicateName(name) => write!(f, "{} is already defined", name),
+            AsmErrorKind::RecursiveMacro(name) => write!(f, "macro {} expands itself", name),
+            AsmErrorKind::IncludeNotFound(path) => write!(f, "cannot read {}", path),
+            AsmErrorKind::RecursiveInclude(path) => write!(f, "{} includes itself", path),
+            AsmErrorKind::UnknownPointer(name) => {
+                write!(f, "@{} after a loop that moves the pointer", name)
+            }
+            AsmErrorKind::UnbalancedLoop(name) => {
+                write!(f, "@{} in a loop that moves the pointer", name)
+            }
+            AsmErrorKind::UnmatchedOpen => write!(f, "unmatched '['"),
+            AsmErrorKind::UnmatchedClose => write!(f, "unmatched ']'"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assembly {
+    /// Names of the root file and of the included ones.
+    pub files: Vec<String>,
+    pub bf: Vec<u8>,
+    /// Where every byte of `bf` comes from.
+    pub map: Vec<Location>,
+}
+
+impl Assembly {
+    /// Compiles the BF output, mapping every instruction to its location.
+    pub fn compile(&self) -> Result<(Vec<Instruction>, Vec<Location>), CompileError> {
+        let (code, offsets) = code::try_compile_with_source_map(self.bf.clone())?;
+        Ok((code, offsets.into_iter().map(|i| self.map[i]).collect()))
+    }
+}
+
+struct Macro {
+    file: usize,
+    body: Range<usize>,
+}
+
+struct Loop {
+    mp: Option<usize>,
+    at: Location,
+    // first `@name` inside the loop
+    moved: Option<(String, Location)>,
+}
+
+struct Assembler<R> {
+    resolve: R,
+    files: Vec<(String, Vec<u8>)>,
+    macros: BTreeMap<String, Macro>,
+    cells: BTreeMap<String, usize>,
+    bf: Vec<u8>,
+    map: Vec<Location>,
+    // pointer, if known
+    mp: Option<usize>,
+    loops: Vec<Loop>,
+    expanding: Vec<String>,
+    including: Vec<usize>,
+}
+
+fn is_ident(b: u8, first: bool) -> bool {
+    b == b'_' || b.is_ascii_alphabetic() || (!first && b.is_ascii_digit())
+}
+
+// `path` joined to the directory of the file named `including`
+fn relative_to(including: &str, path: &str) -> String {
+    match including.rfind('/') {
+        Some(i) if !path.starts_with('/') => format!("{}{}", &including[..=i], path),
+        _ => path.to_string(),
+    }
+}
+
+impl<R: FnMut(&str) -> Option<Vec<u8>>> Assembler<R> {
+    fn error(&self, kind: AsmErrorKind, at: Location) -> AsmError {
+        AsmError {
+            kind,
+            file: self.files[at.file].0.clone(),
+            offset: at.offset,
+        }
+    }
+
+    fn emit(&mut self, op: u8, at: Location) -> Result<(), AsmError> {
+        match op {
+            code::SHR => self.mp = self.mp.map(|mp| mp + 1),
+            code::SHL => self.mp = self.mp.and_then(|mp| mp.checked_sub(1)),
+            code::LB => self.loops.push(Loop {
+                mp: self.mp,
+                at,
+                moved: None,
+            }),
+            code::RB => {
+                let l = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| self.error(AsmErrorKind::UnmatchedClose, at))?;
+                if l.mp != self.mp {
+                    if let Some((name, at)) = l.moved {
+                        return Err(self.error(AsmErrorKind::UnbalancedLoop(name), at));
+                    }
+                    self.mp = None;
+                }
+                if let (Some(outer), Some(moved)) = (self.loops.last_mut(), l.moved) {
+                    outer.moved.get_or_insert(moved);
+                }
+            }
+            _ => {}
+        }
+        self.bf.push(op);
+        self.map.push(at);
+        Ok(())
+    }
+
+    // skips whitespace and comments
+    fn skip(&self, file: usize, mut pos: usize, end: usize) -> usize {
+        let src = &self.files[file].1;
+        while pos < end {
+            match src[pos] {
+                b'#' => {
+                    while pos < end && src[pos] != b'\n' {
+                        pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        pos
+    }
+
+    fn ident(&self, file: usize, pos: usize, end: usize) -> Option<(String, usize)> {
+        let src = &self.files[file].1;
+        if pos == end || !is_ident(src[pos], true) {
+            return None;
+        }
+        let mut stop = pos + 1;
+        while stop < end && is_ident(src[stop], false) {
+            stop += 1;
+        }
+        let name = String::from_utf8_lossy(&src[pos..stop]).to_string();
+        Some((name, stop))
+    }
+
+    fn number(&self, file: usize, pos: usize, end: usize) -> Option<(usize, usize)> {
+        let src = &self.files[file].1;
+        let mut stop = pos;
+        while stop < end && src[stop].is_ascii_digit() {
+            stop += 1;
+        }
+        let digits = core::str::from_utf8(&src[pos..stop]).ok()?;
+        Some((digits.parse().ok()?, stop))
+    }
+
+    fn assemble(&mut self, file: usize, range: Range<usize>) -> Result<(), AsmError> {
+        let end = range.end;
+        let mut pos = self.skip(file, range.start, end);
+        while pos < end {
+            let at = Location { file, offset: pos };
+            let b = self.files[file].1[pos];
+            pos = match b {
+                code::SHL
+                | code::SHR
+                | code::ADD
+                | code::SUB
+                | code::GETCHAR
+                | code::PUTCHAR
+                | code::LB
+                | code::RB => {
+                    let (count, next) = match self.files[file].1.get(pos + 1) {
+                        Some(b'*') => self
+                            .number(file, pos + 2, end)
+                            .ok_or_else(|| self.error(AsmErrorKind::Expected("a count"), at))?,
+                        _ => (1, pos + 1),
+                    };
+                    for _ in 0..count {
+                        self.emit(b, at)?;
+                    }
+                    next
+                }
+                b'@' => {
+                    let (name, next) = self
+                        .ident(file, pos + 1, end)
+                        .ok_or_else(|| self.error(AsmErrorKind::Expected("a cell name"), at))?;
+                    let target = *self
+                        .cells
+                        .get(&name)
+                        .ok_or_else(|| self.error(AsmErrorKind::UnknownName(name.clone()), at))?;
+                    let mp = self.mp.ok_or_else(|| {
+                        self.error(AsmErrorKind::UnknownPointer(name.clone()), at)
+                    })?;
+                    if let Some(l) = self.loops.last_mut() {
+                        l.moved.get_or_insert((name, at));
+                    }
+                    let op = if target > mp { code::SHR } else { code::SHL };
+                    for _ in 0..mp.abs_diff(target) {
+                        self.emit(op, at)?;
+                    }
+                    next
+                }
+                b if is_ident(b, true) => {
+                    let (word, next) = self.ident(file, pos, end).unwrap();
+                    match word.as_str() {
+                        "def" => self.define(file, next, end, at)?,
+                        "cell" => self.cell(file, next, end, at)?,
+                        "include" => self.include(file, next, end, at)?,
+                        _ => {
+                            self.expand(word, at)?;
+                            next
+                        }
+                    }
+                }
+                _ => {
+                    let c = self.files[file].1[pos] as char;
+                    return Err(self.error(AsmErrorKind::UnexpectedChar(c), at));
+                }
+            };
+            pos = self.skip(file, pos, end);
+        }
+        Ok(())
+    }
+
+    fn define(
+        &mut self,
+        file: usize,
+        pos: usize,
+        end: usize,
+        at: Location,
+    ) -> Result<usize, AsmError> {
+        let expected = |what| self.error(AsmErrorKind::Expected(what), at);
+        let pos = self.skip(file, pos, end);
+        let (name, pos) = self
+            .ident(file, pos, end)
+            .ok_or_else(|| expected("a macro name"))?;
+        let pos = self.skip(file, pos, end);
+        let src = &self.files[file].1;
+        if src.get(pos) != Some(&b'{') {
+            return Err(expected("'{'"));
+        }
+        let mut depth = 0;
+        let mut close = pos;
+        while close < end {
+            match src[close] {
+                // a brace in a comment is not part of the body's nesting
+                b'#' => {
+                    while close < end && src[close] != b'\n' {
+                        close += 1;
+                    }
+                    continue;
+                }
+                b'{' => depth += 1,
+                b'}' if depth == 1 => break,
+                b'}' => depth -= 1,
+                _ => {}
+            }
+            close += 1;
+        }
+        if close == end {
+            return Err(expected("'}'"));
+        }
+        if self.macros.contains_key(&name) || self.cells.contains_key(&name) {
+            return Err(self.error(AsmErrorKind::DuplicateName(name), at));
+        }
+        let body = pos + 1..close;
+        self.macros.insert(name, Macro { file, body });
+        Ok(close + 1)
+    }
+
+    fn cell(
+        &mut self,
+        file: usize,
+        pos: usize,
+        end: usize,
+        at: Location,
+    ) -> Result<usize, AsmError> {
+        let pos = self.skip(file, pos, end);
+        let (name, pos) = self
+            .ident(file, pos, end)
+            .ok_or_else(|| self.error(AsmErrorKind::Expected("a cell name"), at))?;
+        let pos = self.skip(file, pos, end);
+        let (index, pos) = self
+            .number(file, pos, end)
+            .ok_or_else(|| self.error(AsmErrorKind::Expected("a cell index"), at))?;
+        if self.macros.contains_key(&name) || self.cells.contains_key(&name) {
+            return Err(self.error(AsmErrorKind::DuplicateName(name), at));
+        }
+        self.cells.insert(name, index);
+        Ok(pos)
+    }
+
+    fn include(
+        &mut self,
+        file: usize,
+        pos: usize,
+        end: usize,
+        at: Location,
+    ) -> Result<usize, AsmError> {
+        let pos = self.skip(file, pos, end);
+        let src = &self.files[file].1;
+        let close = match src.get(pos) {
+            Some(b'"') => src[pos + 1..end].iter().position(|&b| b == b'"'),
+            _ => None,
+        }
+        .ok_or_else(|| self.error(AsmErrorKind::Expected("a quoted path"), at))?;
+        let path = String::from_utf8_lossy(&src[pos + 1..pos + 1 + close]);
+        let path = relative_to(&self.files[file].0, &path);
+
+        let index = match self.files.iter().position(|(name, _)| *name == path) {
+            Some(index) if self.including.contains(&index) => {
+                return Err(self.error(AsmErrorKind::RecursiveInclude(path), at))
+            }
+            // already assembled
+            Some(_) => return Ok(pos + close + 2),
+            None => {
+                let source = (self.resolve)(&path)
+                    .ok_or_else(|| self.error(AsmErrorKind::IncludeNotFound(path.clone()), at))?;
+                self.files.push((path, source));
+                self.files.len() - 1
+            }
+        };
+        self.including.push(index);
+        self.assemble(index, 0..self.files[index].1.len())?;
+        self.including.pop();
+        Ok(pos + close + 2)
+    }
+
+    fn expand(&mut self, name: String, at: Location) -> Result<(), AsmError> {
+        let (file, body) = match self.macros.get(&name) {
+            Some(m) => (m.file, m.body.clone()),
+            None => return Err(self.error(AsmErrorKind::UnknownName(name), at)),
+        };
+        if self.expanding.contains(&name) {
+            return Err(self.error(AsmErrorKind::RecursiveMacro(name), at));
+        }
+        self.expanding.push(name);
+        self.assemble(file, body)?;
+        self.expanding.pop();
+        Ok(())
+    }
+}
+
+/// Assembles `source`, named `name` in errors and in [`Assembly::files`].
+/// `resolve` returns the contents of included files, given their path joined
+/// to the directory of the including file (for the root, the one in `name`).
+pub fn assemble(
+    name: &str,
+    source: &[u8],
+    resolve: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Result<Assembly, AsmError> {
+    let mut asm = Assembler {
+        resolve,
+        files: vec![(name.to_string(), source.to_vec())],
+        macros: BTreeMap::new(),
+        cells: BTreeMap::new(),
+        bf: Vec::new(),
+        map: Vec::new(),
+        mp: Some(0),
+        loops: Vec::new(),
+        expanding: Vec::new(),
+        including: vec![0],
+    };
+    asm.assemble(0, 0..source.len())?;
+    if let Some(l) = asm.loops.pop() {
+        return Err(asm.error(AsmErrorKind::UnmatchedOpen, l.at));
+    }
+    Ok(Assembly {
+        files: asm.files.into_iter().map(|(name, _)| name).collect(),
+        bf: asm.bf,
+        map: asm.map,
+    })
+}
+
+/// Assembles the file at `path`, reading every include relative to the
+/// directory of the file that includes it.
+#[cfg(feature = "std")]
+pub fn assemble_file(path: &std::path::Path) -> Result<Assembly, AsmError> {
+    let name = path.display().to_string();
+    let source = std::fs::read(path).map_err(|_| AsmError {
+        kind: AsmErrorKind::IncludeNotFound(name.clone()),
+        file: name.clone(),
+        offset: 0,
+    })?;
+    assemble(&name, &source, |include| std::fs::read(include).ok())
+}
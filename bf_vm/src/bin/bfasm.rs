@@ -0,0 +1,30 @@
+use bf_vm::asm::assemble_file;
+
+const USAGE: &str = "\
+usage:
+  bfasm <program.bfa> [-o <program.bf>]
+
+Lowers an assembler source to BF, printed or written to the -o file.";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (path, out) = match &args[..] {
+        [path] => (path, None),
+        [path, o, out] if o == "-o" => (path, Some(out)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let assembly = assemble_file(path.as_ref()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    match out {
+        Some(out) => std::fs::write(out, &assembly.bf).unwrap_or_else(|e| {
+            eprintln!("failed to write {}: {}", out, e);
+            std::process::exit(1);
+        }),
+        None => println!("{}", String::from_utf8_lossy(&assembly.bf)),
+    }
+}
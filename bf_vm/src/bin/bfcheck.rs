@@ -0,0 +1,61 @@
+use bf_vm::analyze::{analyze, line_col};
+
+const USAGE: &str = "\
+usage:
+  bfcheck <program.bf>... [--bits <n>]
+
+Reports problems found without running the programs, and the number of tape
+cells each one can touch. Cells have 1 to 64 bits, 8 by default. Exits with 1
+if any program has an error.";
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    std::process::exit(1);
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut paths = vec![];
+    let mut bits = 8;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--bits" => {
+                let value = iter
+                    .next()
+                    .unwrap_or_else(|| fail(format!("{} expects a value", arg)));
+                bits = value
+                    .parse()
+                    .ok()
+                    .filter(|bits| (1..=64).contains(bits))
+                    .unwrap_or_else(|| fail(format!("invalid value {} for {}", value, arg)));
+            }
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.is_empty() {
+        fail("expected a program");
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        let source = std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("failed to read {}: {}", path, e);
+            std::process::exit(1);
+        });
+        let analysis = analyze(&source, bits);
+        for d in &analysis.diagnostics {
+            let (line, col) = line_col(&source, d.span.start);
+            println!("{}:{}:{}: {}", path, line, col, d);
+        }
+        match analysis.tape_bound {
+            Some(cells) => println!("{}: at most {} cells", path, cells),
+            None => println!("{}: tape usage unbounded", path),
+        }
+        failed |= analysis.has_errors();
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
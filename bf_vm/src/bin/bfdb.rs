@@ -0,0 +1,167 @@
+use bf_vm::code;
+use bf_vm::debugger::{Breakpoint, Debugger, StopReason};
+use bf_vm::interpreter::{FieldExt, Interpreter};
+use halo2_proofs::halo2curves::bn256::Fr;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+commands:
+  s, step [n]        execute n instructions (default 1)
+  c, continue        run until a breakpoint, a watchpoint or halt
+  b, break <ip>      break before the instruction at ip
+  bs <pos>           break before the instruction at source byte offset pos
+  d, delete <ip>     remove an ip breakpoint (ds <pos> for source breakpoints)
+  w, watch <cell>    stop when a tape cell changes (uw <cell> to remove)
+  i, info            list breakpoints and watchpoints
+  r, regs            print the registers
+  m, mem [from] [to] print tape cells
+  q, quit            exit";
+
+fn val(v: &Fr) -> u128 {
+    v.get_lower_128()
+}
+
+fn print_regs(dbg: &Debugger) {
+    let r = dbg.register();
+    let ci = val(&r.current_instruction) as u8;
+    let ci = if ci == 0 { ' ' } else { ci as char };
+    println!(
+        "clk={} ip={} ci='{}' arg={} mp={} mv={}{}",
+        val(&r.cycle),
+        val(&r.instruction_pointer),
+        ci,
+        val(&r.operand),
+        val(&r.memory_pointer),
+        val(&r.memory_value),
+        match dbg.source_position() {
+            Some(pos) => format!(" src={}", pos),
+            None => String::new(),
+        }
+    );
+}
+
+fn print_mem(dbg: &Debugger, from: usize, to: usize) {
+    let mp = dbg.register().mp();
+    let cells = (from..to)
+        .map(|i| {
+            let v = val(&dbg.cell(i));
+            if i == mp {
+                format!("[{}]", v)
+            } else {
+                format!("{}", v)
+            }
+        })
+        .collect::<Vec<_>>();
+    println!("{}..{}: {}", from, to, cells.join(" "));
+}
+
+fn report(dbg: &Debugger, reason: StopReason) {
+    match reason {
+        StopReason::Step => {}
+        StopReason::Breakpoint(bp) => println!("breakpoint {:?}", bp),
+        StopReason::Watchpoint { cell, old, new } => {
+            println!("cell {} changed: {} -> {}", cell, val(&old), val(&new))
+        }
+        StopReason::Trap(trap) => println!("trap: {}", trap),
+        StopReason::Halted => println!("program halted"),
+    }
+    print_regs(dbg);
+}
+
+fn parse(arg: Option<&str>) -> Option<usize> {
+    arg.and_then(|a| a.parse().ok())
+}
+
+fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        eprintln!("usage: {} <program.bf> [input]", args[0]);
+        std::process::exit(1);
+    }
+    let source = std::fs::read(&args[1]).expect("failed to read program");
+    let (program, source_map) = code::try_compile_with_source_map(source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", args[1], e);
+        std::process::exit(1);
+    });
+
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    if let Some(input) = args.get(2) {
+        vm.set_input(code::easygen(input));
+    }
+    let mut dbg = Debugger::new(vm).with_source_map(source_map);
+    print_regs(&dbg);
+
+    let stdin = io::stdin();
+    loop {
+        print!("(bfdb) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => continue,
+        };
+        let arg = words.next();
+        let arg2 = words.next();
+        match cmd {
+            "s" | "step" => {
+                let mut reason = StopReason::Step;
+                for _ in 0..parse(arg).unwrap_or(1) {
+                    reason = dbg.step();
+                    if reason != StopReason::Step {
+                        break;
+                    }
+                }
+                report(&dbg, reason);
+            }
+            "c" | "continue" => {
+                let reason = dbg.cont();
+                report(&dbg, reason);
+            }
+            "b" | "break" | "bs" | "d" | "delete" | "ds" => match parse(arg) {
+                Some(n) => {
+                    let bp = if cmd == "bs" || cmd == "ds" {
+                        Breakpoint::Source(n)
+                    } else {
+                        Breakpoint::Ip(n)
+                    };
+                    if cmd.starts_with('d') {
+                        dbg.remove_breakpoint(bp);
+                    } else {
+                        dbg.add_breakpoint(bp);
+                    }
+                }
+                None => println!("expected a number"),
+            },
+            "w" | "watch" | "uw" => match parse(arg) {
+                Some(cell) if cmd == "uw" => {
+                    dbg.unwatch(cell);
+                }
+                Some(cell) => {
+                    dbg.watch(cell);
+                }
+                None => println!("expected a cell index"),
+            },
+            "i" | "info" => {
+                for bp in dbg.breakpoints() {
+                    println!("breakpoint {:?}", bp);
+                }
+                for cell in dbg.watchpoints() {
+                    println!("watchpoint cell {}", cell);
+                }
+            }
+            "r" | "regs" => print_regs(&dbg),
+            "m" | "mem" => {
+                let from = parse(arg).unwrap_or(0);
+                let to = parse(arg2).unwrap_or_else(|| dbg.tape().len().max(from + 1));
+                print_mem(&dbg, from, to);
+            }
+            "q" | "quit" => break,
+            _ => println!("{}", HELP),
+        }
+    }
+}
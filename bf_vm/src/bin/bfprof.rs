@@ -0,0 +1,75 @@
+use bf_vm::code;
+use bf_vm::interpreter::Interpreter;
+use bf_vm::profile::Profiler;
+
+const USAGE: &str = "\
+usage:
+  bfprof <program.bf> [--input <text>] [--bits <n>] [--top <n>] [--folded <file>]
+
+Prints where the cycles of a run go. --folded also writes the loop stacks in
+the folded format of flamegraph tools.";
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: &str, value: String) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid value {} for {}", value, arg)))
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut positional = vec![];
+    let mut input = String::new();
+    let mut bits = 8;
+    let mut top = 10;
+    let mut folded = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .unwrap_or_else(|| fail(format!("{} expects a value", arg)))
+        };
+        match arg.as_str() {
+            "--input" => input = value(),
+            "--bits" => bits = parse(arg, value()),
+            "--top" => top = parse(arg, value()),
+            "--folded" => folded = Some(value()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    let path = match &positional[..] {
+        [path] => path,
+        _ => fail("expected one program"),
+    };
+
+    let source = std::fs::read(path).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", path, e);
+        std::process::exit(1);
+    });
+    let (program, source_map) = code::try_compile_with_source_map(source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+
+    let mut profiler = Profiler::new().with_source_map(source_map).with_bits(bits);
+    let mut vm = Interpreter::new();
+    vm.set_bits(bits);
+    vm.set_code(program);
+    vm.set_input(code::easygen(&input));
+    if let Err(trap) = vm.try_run_traced(&mut profiler) {
+        eprintln!("{}: {}", path, trap);
+    }
+    print!("{}", profiler.report(top));
+    if let Some(file) = folded {
+        std::fs::write(&file, profiler.folded()).unwrap_or_else(|e| {
+            eprintln!("failed to write {}: {}", file, e);
+            std::process::exit(1);
+        });
+    }
+}
@@ -0,0 +1,62 @@
+use bf_vm::code;
+use bf_vm::interpreter::Interpreter;
+use bf_vm::io::{Reader, Writer};
+
+const USAGE: &str = "\
+usage:
+  bfrun <program.bf> [--bits <n>] [--eof zero|max|unchanged]
+                     [--arithmetic wrapping|strict]
+
+Runs the program on stdin and stdout without recording its trace.";
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    std::process::exit(1);
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut positional = vec![];
+    let mut vm = Interpreter::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .unwrap_or_else(|| fail(format!("{} expects a value", arg)))
+        };
+        match arg.as_str() {
+            "--bits" => {
+                let bits = value();
+                vm.set_bits(
+                    bits.parse()
+                        .unwrap_or_else(|_| fail(format!("invalid value {} for --bits", bits))),
+                )
+            }
+            "--eof" => vm.set_eof(value().parse().unwrap_or_else(|e| fail(e))),
+            "--arithmetic" => vm.set_arithmetic(value().parse().unwrap_or_else(|e| fail(e))),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    let path = match &positional[..] {
+        [path] => path,
+        _ => fail("expected one program"),
+    };
+
+    let source = std::fs::read(path).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", path, e);
+        std::process::exit(1);
+    });
+    let program = code::try_compile(source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+    vm.set_code(program);
+    vm.set_reader(Reader(std::io::stdin()));
+    vm.set_writer(Writer(std::io::stdout()));
+    if let Err(trap) = vm.try_run_into(&mut ()) {
+        eprintln!("{}: {}", path, trap);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,83 @@
+use bf_vm::code;
+use bf_vm::dump::{self, Format, Table};
+use bf_vm::interpreter::Interpreter;
+use bf_vm::matrix::Matrix;
+
+const USAGE: &str = "\
+usage:
+  bftrace dump <program.bf> [--input <text>] [--table <name>] [--csv]
+  bftrace diff <a.bf> <b.bf> [--input <text>] [--input-b <text>] [--context <n>]
+
+tables: program, processor, instruction, memory, input, output";
+
+fn trace(path: &str, input: &str) -> Matrix {
+    let source = std::fs::read(path).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", path, e);
+        std::process::exit(1);
+    });
+    let program = code::try_compile(source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen(input));
+    vm.run();
+    vm.matrix
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut positional = vec![];
+    let mut input = String::new();
+    let mut input_b = None;
+    let mut table = None;
+    let mut format = Format::Pretty;
+    let mut context = 3;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next().cloned().unwrap_or_else(|| {
+                eprintln!("{} expects a value\n{}", arg, USAGE);
+                std::process::exit(1);
+            })
+        };
+        match arg.as_str() {
+            "--input" => input = value(),
+            "--input-b" => input_b = Some(value()),
+            "--table" => {
+                let name = value();
+                table = Some(Table::from_name(&name).unwrap_or_else(|| {
+                    eprintln!("unknown table {}\n{}", name, USAGE);
+                    std::process::exit(1);
+                }))
+            }
+            "--context" => context = value().parse().expect("context must be a number"),
+            "--csv" => format = Format::Csv,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    match positional.iter().map(|s| s.as_str()).collect::<Vec<_>>()[..] {
+        ["dump", path] => {
+            let matrix = trace(path, &input);
+            match table {
+                Some(table) => print!("{}", dump::render_table(&matrix, table, format)),
+                None => print!("{}", dump::render_matrix(&matrix, format)),
+            }
+        }
+        ["diff", a, b] => {
+            let left = trace(a, &input);
+            let right = trace(b, input_b.as_deref().unwrap_or(&input));
+            print!("{}", dump::render_diff(&left, &right, context));
+            if dump::diff(&left, &right).is_some() {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
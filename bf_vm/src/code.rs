@@ -1,7 +1,7 @@
 extern crate alloc;
 
 use alloc::vec::Vec;
-use core::convert::From;
+use core::fmt;
 use halo2_proofs::halo2curves::bn256::Fr;
 
 pub const SHL: u8 = 0x3C;
@@ -20,37 +20,116 @@ pub fn easygen(code: &str) -> Vec<Fr> {
         .collect()
 }
 
-/**
- * Alan's implementation employs a direct target address for jump operations,
- * causing incompatibility with u8 for larger programs.
- * Using u16 is a temporary measure to accommodate all test cases.
- * Once hash-based public input verification is implemented, this can be removed.
- */
-pub fn compile_to_u16(code: Vec<u8>) -> Vec<u16> {
-    let filter = vec![SHL, SHR, ADD, SUB, GETCHAR, PUTCHAR, LB, RB];
-    let mut instrs = Vec::<u16>::new();
-    let mut jstack = Vec::<u16>::new();
-    for i in code {
+/// Jump operands are `u32`, the index one past the last instruction included.
+pub const MAX_INSTRUCTIONS: usize = u32::MAX as usize;
+
+/// A compiled instruction. Only the jumps use `operand`: the index of the
+/// instruction following the matching bracket, where `[` jumps to when the
+/// current cell is zero and `]` jumps to when it is not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: u8,
+    pub operand: u32,
+}
+
+impl Instruction {
+    pub fn new(opcode: u8, operand: u32) -> Self {
+        Self { opcode, operand }
+    }
+}
+
+/// Errors of [`try_compile`], positions are byte offsets in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+    /// The program has more than [`MAX_INSTRUCTIONS`] instructions.
+    TooManyInstructions,
+    UnmatchedOpen {
+        pos: usize,
+    },
+    UnmatchedClose {
+        pos: usize,
+    },
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::TooManyInstructions => {
+                write!(f, "program has more than {} instructions", MAX_INSTRUCTIONS)
+            }
+            CompileError::UnmatchedOpen { pos } => write!(f, "unmatched '[' at offset {}", pos),
+            CompileError::UnmatchedClose { pos } => write!(f, "unmatched ']' at offset {}", pos),
+        }
+    }
+}
+
+/// Same as [`try_compile`], but also returns for every instruction the byte
+/// offset in `code` it came from.
+pub fn try_compile_with_source_map(
+    code: Vec<u8>,
+) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+    let filter = [SHL, SHR, ADD, SUB, GETCHAR, PUTCHAR, LB, RB];
+    let mut instrs = Vec::<Instruction>::new();
+    let mut source_map = Vec::<usize>::new();
+    // (instruction index, source offset) of the open brackets
+    let mut jstack = Vec::<(usize, usize)>::new();
+    for (pos, i) in code.into_iter().enumerate() {
         if !filter.contains(&i) {
             continue;
         }
-        instrs.push(i as u16);
+        if instrs.len() == MAX_INSTRUCTIONS {
+            return Err(CompileError::TooManyInstructions);
+        }
+        instrs.push(Instruction::new(i, 0));
+        source_map.push(pos);
         if i == LB {
-            instrs.push(0);
-            jstack.push(instrs.len() as u16 - 1);
+            jstack.push((instrs.len() - 1, pos));
         }
         if i == RB {
-            instrs.push(*jstack.last().unwrap() + 1);
-            instrs[*jstack.last().unwrap() as usize] = instrs.len() as u16;
-            jstack.pop();
+            let (lb, _) = jstack.pop().ok_or(CompileError::UnmatchedClose { pos })?;
+            instrs[lb].operand = instrs.len() as u32;
+            instrs.last_mut().unwrap().operand = lb as u32 + 1;
         }
     }
-    return instrs;
+    if let Some((_, pos)) = jstack.pop() {
+        return Err(CompileError::UnmatchedOpen { pos });
+    }
+    Ok((instrs, source_map))
 }
 
-pub fn compile(code: Vec<u8>) -> Vec<Fr> {
-    compile_to_u16(code)
-        .into_iter()
-        .map(|x| Fr::from(x as u64))
-        .collect()
+/// Every unmatched bracket of `code`, where [`try_compile`] only reports the
+/// first one.
+pub fn unmatched_brackets(code: &[u8]) -> Vec<CompileError> {
+    let mut errors = Vec::new();
+    let mut open = Vec::new();
+    for (pos, &i) in code.iter().enumerate() {
+        if i == LB {
+            open.push(pos);
+        } else if i == RB && open.pop().is_none() {
+            errors.push(CompileError::UnmatchedClose { pos });
+        }
+    }
+    errors.extend(
+        open.into_iter()
+            .map(|pos| CompileError::UnmatchedOpen { pos }),
+    );
+    errors.sort_by_key(|e| match e {
+        CompileError::UnmatchedOpen { pos } | CompileError::UnmatchedClose { pos } => *pos,
+        CompileError::TooManyInstructions => 0,
+    });
+    errors
+}
+
+pub fn try_compile(code: Vec<u8>) -> Result<Vec<Instruction>, CompileError> {
+    try_compile_with_source_map(code).map(|(instrs, _)| instrs)
+}
+
+/// Panicking version of [`try_compile_with_source_map`].
+pub fn compile_with_source_map(code: Vec<u8>) -> (Vec<Instruction>, Vec<usize>) {
+    try_compile_with_source_map(code).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Panicking version of [`try_compile`].
+pub fn compile(code: Vec<u8>) -> Vec<Instruction> {
+    compile_with_source_map(code).0
 }
@@ -0,0 +1,166 @@
+use crate::interpreter::{Interpreter, Register, Trap};
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use halo2_proofs::halo2curves::bn256::Fr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Breakpoint {
+    /// Stop before executing the instruction at this instruction pointer.
+    Ip(usize),
+    /// Stop before executing the instruction at this byte offset of the source.
+    Source(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Step,
+    Breakpoint(Breakpoint),
+    Watchpoint {
+        cell: usize,
+        old: Fr,
+        new: Fr,
+    },
+    /// The current instruction traps and was not executed.
+    Trap(Trap),
+    Halted,
+}
+
+/// Drives an [`Interpreter`] one instruction at a time. The interpreter keeps
+/// its registers, tape and matrix, so a fully stepped session produces the same
+/// trace as [`Interpreter::run`].
+pub struct Debugger {
+    pub vm: Interpreter,
+    source_map: Vec<usize>,
+    breakpoints: BTreeSet<Breakpoint>,
+    watchpoints: BTreeSet<usize>,
+    finalized: bool,
+}
+
+impl Debugger {
+    pub fn new(mut vm: Interpreter) -> Self {
+        vm.init();
+        Self {
+            vm,
+            source_map: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            finalized: false,
+        }
+    }
+
+    /// Attaches the map produced by [`crate::code::compile_with_source_map`],
+    /// required for [`Breakpoint::Source`].
+    pub fn with_source_map(mut self, source_map: Vec<usize>) -> Self {
+        self.source_map = source_map;
+        self
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        self.breakpoints.insert(breakpoint)
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        self.breakpoints.remove(&breakpoint)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    pub fn watch(&mut self, cell: usize) -> bool {
+        self.watchpoints.insert(cell)
+    }
+
+    pub fn unwatch(&mut self, cell: usize) -> bool {
+        self.watchpoints.remove(&cell)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.iter()
+    }
+
+    pub fn register(&self) -> &Register {
+        &self.vm.register
+    }
+
+    pub fn tape(&self) -> &[Fr] {
+        &self.vm.memory
+    }
+
+    /// Value of a tape cell; cells the program has not reached yet read as zero.
+    pub fn cell(&self, index: usize) -> Fr {
+        self.vm.memory.get(index).copied().unwrap_or_else(Fr::zero)
+    }
+
+    /// Source byte offset of the current instruction, if a source map is attached.
+    pub fn source_position(&self) -> Option<usize> {
+        self.source_map.get(self.vm.register.ip()).copied()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.vm.is_halted()
+    }
+
+    /// Executes a single instruction. Watchpoints are reported, breakpoints are not.
+    pub fn step(&mut self) -> StopReason {
+        if self.vm.is_halted() {
+            self.finish();
+            return StopReason::Halted;
+        }
+        let watched = self
+            .watchpoints
+            .iter()
+            .map(|&cell| (cell, self.cell(cell)))
+            .collect::<Vec<_>>();
+
+        if let Err(trap) = self.vm.try_step() {
+            return StopReason::Trap(trap);
+        }
+
+        if self.vm.is_halted() {
+            self.finish();
+        }
+        for (cell, old) in watched {
+            let new = self.cell(cell);
+            if new != old {
+                return StopReason::Watchpoint { cell, old, new };
+            }
+        }
+        if self.vm.is_halted() {
+            StopReason::Halted
+        } else {
+            StopReason::Step
+        }
+    }
+
+    /// Runs until a breakpoint or watchpoint triggers or the program halts.
+    /// The current instruction is always executed, so continuing from a
+    /// breakpoint does not stop on it again.
+    pub fn cont(&mut self) -> StopReason {
+        loop {
+            match self.step() {
+                StopReason::Step => {}
+                reason => return reason,
+            }
+            if let Some(breakpoint) = self.hit_breakpoint() {
+                return StopReason::Breakpoint(breakpoint);
+            }
+        }
+    }
+
+    fn hit_breakpoint(&self) -> Option<Breakpoint> {
+        let ip = self.vm.register.ip();
+        let pos = self.source_position();
+        self.breakpoints.iter().copied().find(|bp| match bp {
+            Breakpoint::Ip(target) => *target == ip,
+            Breakpoint::Source(target) => pos == Some(*target),
+        })
+    }
+
+    fn finish(&mut self) {
+        if !self.finalized {
+            self.vm.finalize();
+            self.finalized = true;
+        }
+    }
+}
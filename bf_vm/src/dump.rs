@@ -0,0 +1,283 @@
+use crate::code;
+use crate::matrix::{IOMatrixRow, InstructionMatrixRow, Matrix};
+use alloc::string::String;
+use alloc::vec::Vec;
+use halo2_proofs::halo2curves::bn256::Fr;
+use halo2curves::ff::PrimeField;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Space aligned columns with a header line.
+    Pretty,
+    Csv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Table {
+    Program,
+    Processor,
+    Instruction,
+    Memory,
+    Input,
+    Output,
+}
+
+impl Table {
+    pub const ALL: [Table; 6] = [
+        Table::Program,
+        Table::Processor,
+        Table::Instruction,
+        Table::Memory,
+        Table::Input,
+        Table::Output,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Program => "program",
+            Table::Processor => "processor",
+            Table::Instruction => "instruction",
+            Table::Memory => "memory",
+            Table::Input => "input",
+            Table::Output => "output",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Table> {
+        Table::ALL.iter().copied().find(|t| t.name() == name)
+    }
+
+    pub fn header(&self) -> &'static [&'static str] {
+        match self {
+            Table::Program | Table::Instruction => &["ip", "ci", "ni", "arg"],
+            Table::Processor => &["clk", "ip", "ci", "ni", "arg", "mp", "mv", "mv_inv", "eof"],
+            Table::Memory => &["clk", "mp", "mv", "interweave"],
+            Table::Input | Table::Output => &["clk", "value", "diff"],
+        }
+    }
+
+    /// Decoded rows of this table.
+    pub fn rows(&self, matrix: &Matrix) -> Vec<Vec<String>> {
+        let instruction_rows = |rows: &[InstructionMatrixRow]| {
+            rows.iter()
+                .map(|r| {
+                    vec![
+                        value(&r.instruction_pointer),
+                        opcode(&r.current_instruction),
+                        opcode(&r.next_instruction),
+                        value(&r.operand),
+                    ]
+                })
+                .collect()
+        };
+        let io_rows = |rows: &[IOMatrixRow]| {
+            rows.iter()
+                .map(|r| vec![value(&r.cycle), value(&r.value), value(&r.diff)])
+                .collect()
+        };
+        match self {
+            Table::Program => instruction_rows(&matrix.program),
+            Table::Instruction => instruction_rows(&matrix.instruction_matrix),
+            Table::Processor => matrix
+                .processor_matrix
+                .iter()
+                .map(|r| {
+                    vec![
+                        value(&r.cycle),
+                        value(&r.instruction_pointer),
+                        opcode(&r.current_instruction),
+                        opcode(&r.next_instruction),
+                        value(&r.operand),
+                        value(&r.memory_pointer),
+                        value(&r.memory_value),
+                        value(&r.memory_value_inverse),
+                        value(&r.eof),
+                    ]
+                })
+                .collect(),
+            Table::Memory => matrix
+                .memory_matrix
+                .iter()
+                .map(|r| {
+                    vec![
+                        value(&r.cycle),
+                        value(&r.memory_pointer),
+                        value(&r.memory_value),
+                        value(&r.interweave_indicator),
+                    ]
+                })
+                .collect(),
+            Table::Input => io_rows(&matrix.input_matrix),
+            Table::Output => io_rows(&matrix.output_matrix),
+        }
+    }
+}
+
+/// Renders a field element as a decimal integer when it is small, as a negative
+/// integer when its negation is small, and as big-endian hex otherwise.
+pub fn value(v: &Fr) -> String {
+    let small = |v: &Fr| {
+        let repr = v.to_repr();
+        let repr = repr.as_ref();
+        if repr[8..].iter().all(|&b| b == 0) {
+            Some(u64::from_le_bytes(repr[0..8].try_into().unwrap()))
+        } else {
+            None
+        }
+    };
+    if let Some(n) = small(v) {
+        return format!("{}", n);
+    }
+    if let Some(n) = small(&-*v) {
+        return format!("-{}", n);
+    }
+    let mut hex = String::from("0x");
+    for b in v.to_repr().as_ref().iter().rev() {
+        hex.push_str(&format!("{:02x}", b));
+    }
+    hex
+}
+
+/// Renders an opcode as its character, falling back to [`value`] for the
+/// halting zero.
+pub fn opcode(v: &Fr) -> String {
+    let s = value(v);
+    match s.parse::<u8>() {
+        Ok(op)
+            if [
+                code::SHL,
+                code::SHR,
+                code::ADD,
+                code::SUB,
+                code::GETCHAR,
+                code::PUTCHAR,
+                code::LB,
+                code::RB,
+            ]
+            .contains(&op) =>
+        {
+            format!("{}", op as char)
+        }
+        _ => s,
+    }
+}
+
+pub fn render_rows(header: &[&str], rows: &[Vec<String>], format: Format) -> String {
+    let mut out = String::new();
+    match format {
+        Format::Csv => {
+            out.push_str(&header.join(","));
+            out.push('\n');
+            for row in rows {
+                out.push_str(&row.join(","));
+                out.push('\n');
+            }
+        }
+        Format::Pretty => {
+            let mut widths = header.iter().map(|h| h.len()).collect::<Vec<_>>();
+            for row in rows {
+                for (w, cell) in widths.iter_mut().zip(row) {
+                    *w = (*w).max(cell.len());
+                }
+            }
+            let line = |cells: &mut dyn Iterator<Item = &str>| {
+                let mut s = String::new();
+                for (i, (cell, w)) in cells.zip(&widths).enumerate() {
+                    if i > 0 {
+                        s.push_str("  ");
+                    }
+                    s.push_str(&format!("{:>w$}", cell, w = *w));
+                }
+                s.push('\n');
+                s
+            };
+            out.push_str(&line(&mut header.iter().copied()));
+            for row in rows {
+                out.push_str(&line(&mut row.iter().map(|c| c.as_str())));
+            }
+        }
+    }
+    out
+}
+
+pub fn render_table(matrix: &Matrix, table: Table, format: Format) -> String {
+    render_rows(table.header(), &table.rows(matrix), format)
+}
+
+/// Renders every table, each preceded by a `# name` line.
+pub fn render_matrix(matrix: &Matrix, format: Format) -> String {
+    let mut out = String::new();
+    for table in Table::ALL {
+        out.push_str(&format!("# {}\n", table.name()));
+        out.push_str(&render_table(matrix, table, format));
+        out.push('\n');
+    }
+    out
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub table: Table,
+    pub row: usize,
+    /// `None` when the table of that trace has fewer rows.
+    pub left: Option<Vec<String>>,
+    pub right: Option<Vec<String>>,
+}
+
+/// Finds the first row at which two traces differ, checking the tables in
+/// [`Table::ALL`] order.
+pub fn diff(left: &Matrix, right: &Matrix) -> Option<Divergence> {
+    for table in Table::ALL {
+        let l = table.rows(left);
+        let r = table.rows(right);
+        for row in 0..l.len().max(r.len()) {
+            if l.get(row) != r.get(row) {
+                return Some(Divergence {
+                    table,
+                    row,
+                    left: l.get(row).cloned(),
+                    right: r.get(row).cloned(),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Renders the divergent table of both traces side by side around the first
+/// divergent row, marking it with `>>`.
+pub fn render_diff(left: &Matrix, right: &Matrix, context: usize) -> String {
+    let d = match diff(left, right) {
+        Some(d) => d,
+        None => return String::from("traces are identical\n"),
+    };
+    let l = d.table.rows(left);
+    let r = d.table.rows(right);
+    let header = d.table.header();
+    let from = d.row.saturating_sub(context);
+    let to = (d.row + context + 1).min(l.len().max(r.len()));
+
+    let mut full_header = vec!["", "row"];
+    full_header.extend(header.iter().copied());
+    full_header.push("|");
+    full_header.extend(header.iter().copied());
+    let missing = || vec![String::from("~"); header.len()];
+    let rows = (from..to)
+        .map(|i| {
+            let mark = if i == d.row { ">>" } else { "" };
+            let mut row = vec![String::from(mark), format!("{}", i)];
+            row.extend(l.get(i).cloned().unwrap_or_else(missing));
+            row.push(String::from("|"));
+            row.extend(r.get(i).cloned().unwrap_or_else(missing));
+            row
+        })
+        .collect::<Vec<_>>();
+
+    let mut out = format!(
+        "first divergence in {} table at row {}\n",
+        d.table.name(),
+        d.row
+    );
+    out.push_str(&render_rows(&full_header, &rows, Format::Pretty));
+    out
+}
@@ -1,23 +1,35 @@
-use crate::code;
-use crate::matrix::{IOMatrixRow, InstructionMatrixRow, Matrix, MemoryMatrixRow};
+use crate::code::{self, Instruction};
+use crate::io::{ByteSink, ByteSource, IoError};
+use crate::matrix::{IOMatrixRow, InstructionMatrixRow, Matrix};
+use crate::sink::TraceSink;
+use crate::tracer::{Recorder, Tracer};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::convert::From;
+use core::fmt;
+use core::str::FromStr;
 use halo2_proofs::arithmetic::Field;
 use halo2_proofs::halo2curves::bn256::Fr;
 use halo2curves::ff::PrimeField;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Register {
     pub cycle: Fr,
     pub instruction_pointer: Fr,
     pub current_instruction: Fr,
     pub next_instruction: Fr,
+    pub operand: Fr,
     pub memory_pointer: Fr,
     pub memory_value: Fr,
     pub memory_value_inverse: Fr,
+    /// 0 until a `,` finds the input exhausted, 1 from that `,` on. A `,`
+    /// under the flag stores the [`EofPolicy`] value.
+    pub eof: Fr,
 }
 
-trait FieldExt {
+pub trait FieldExt {
     fn get_lower_128(&self) -> u128;
 }
 
@@ -38,23 +50,167 @@ impl FieldExt for Fr {
     }
 }
 
+/// What `,` stores once the input is exhausted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EofPolicy {
+    #[default]
+    Zero,
+    /// -1, i.e. `2^bits - 1`.
+    Max,
+    /// Leaves the cell unchanged.
+    Unchanged,
+}
+
+impl EofPolicy {
+    /// Value `,` stores at EOF into a cell holding `current`.
+    pub fn value(self, current: Fr, bits: u64) -> Fr {
+        match self {
+            EofPolicy::Zero => Fr::zero(),
+            EofPolicy::Max => Fr::from((1 << bits) - 1),
+            EofPolicy::Unchanged => current,
+        }
+    }
+}
+
+impl fmt::Display for EofPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EofPolicy::Zero => write!(f, "zero"),
+            EofPolicy::Max => write!(f, "max"),
+            EofPolicy::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+impl FromStr for EofPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zero" => Ok(EofPolicy::Zero),
+            "max" => Ok(EofPolicy::Max),
+            "unchanged" => Ok(EofPolicy::Unchanged),
+            _ => Err(format!("unknown EOF policy {}", s)),
+        }
+    }
+}
+
+/// How `+` and `-` treat the ends of the cell range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Arithmetic {
+    /// Wrap around modulo `2^bits`.
+    #[default]
+    Wrapping,
+    /// `+` at `2^bits - 1` and `-` at 0 trap.
+    Strict,
+}
+
+impl fmt::Display for Arithmetic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arithmetic::Wrapping => write!(f, "wrapping"),
+            Arithmetic::Strict => write!(f, "strict"),
+        }
+    }
+}
+
+impl FromStr for Arithmetic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrapping" => Ok(Arithmetic::Wrapping),
+            "strict" => Ok(Arithmetic::Strict),
+            _ => Err(format!("unknown arithmetic mode {}", s)),
+        }
+    }
+}
+
+/// An instruction that cannot execute: `<` on cell 0, a wrap under
+/// [`Arithmetic::Strict`], or failed I/O. The interpreter stops before it,
+/// without recording its rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trap {
+    /// `+` on a cell holding `2^bits - 1`.
+    Overflow { cycle: u64, ip: usize, mp: usize },
+    /// `-` on a cell holding 0.
+    Underflow { cycle: u64, ip: usize, mp: usize },
+    /// `<` on cell 0, the tape has no cells to its left.
+    TapeUnderflow { cycle: u64, ip: usize },
+    /// The reader or writer of `,` or `.` failed, or flushing the writer
+    /// after the run did (then `ip` is past the program).
+    Io { cycle: u64, ip: usize },
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trap::Overflow { cycle, ip, mp } => write!(
+                f,
+                "'+' overflows cell {} at ip {} (cycle {})",
+                mp, ip, cycle
+            ),
+            Trap::Underflow { cycle, ip, mp } => write!(
+                f,
+                "'-' underflows cell {} at ip {} (cycle {})",
+                mp, ip, cycle
+            ),
+            Trap::TapeUnderflow { cycle, ip } => {
+                write!(f, "'<' moves left of cell 0 at ip {} (cycle {})", ip, cycle)
+            }
+            Trap::Io { cycle, ip } => write!(f, "I/O error at ip {} (cycle {})", ip, cycle),
+        }
+    }
+}
+
 impl Register {
-    fn ip(&self) -> usize {
+    pub fn ip(&self) -> usize {
         self.instruction_pointer.get_lower_128() as usize
     }
 
-    fn mp(&self) -> usize {
+    pub fn mp(&self) -> usize {
         self.memory_pointer.get_lower_128() as usize
     }
 }
 
+/// Rows of the program table for compiled `code`, one per instruction.
+pub fn program_rows(code: &[Instruction]) -> Vec<InstructionMatrixRow> {
+    (0..code.len())
+        .map(|i| InstructionMatrixRow {
+            instruction_pointer: Fr::from(i as u64),
+            current_instruction: opcode(code, i),
+            next_instruction: opcode(code, i + 1),
+            operand: operand(code, i),
+        })
+        .collect()
+}
+
+/// Opcode at `ip`, 0 past the end of the program.
+fn opcode(code: &[Instruction], ip: usize) -> Fr {
+    code.get(ip)
+        .map_or(Fr::zero(), |i| Fr::from(i.opcode as u64))
+}
+
+fn operand(code: &[Instruction], ip: usize) -> Fr {
+    code.get(ip)
+        .map_or(Fr::zero(), |i| Fr::from(i.operand as u64))
+}
+
 pub struct Interpreter {
-    pub code: Vec<Fr>,
+    pub code: Vec<Instruction>,
     pub input: Vec<Fr>,
     pub memory: Vec<Fr>,
     pub register: Register,
     pub matrix: Matrix,
     pub bits: u64,
+    pub eof: EofPolicy,
+    pub arithmetic: Arithmetic,
+    /// Bytes written by `.` while no writer is set.
+    pub output: Vec<u8>,
+    reader: Option<Box<dyn ByteSource>>,
+    writer: Option<Box<dyn ByteSink>>,
+    last_input_clk: Option<Fr>,
+    last_output_clk: Option<Fr>,
 }
 
 impl Interpreter {
@@ -66,193 +222,326 @@ impl Interpreter {
             register: Register::default(),
             matrix: Matrix::default(),
             bits: 8,
+            eof: EofPolicy::default(),
+            arithmetic: Arithmetic::default(),
+            output: Vec::new(),
+            reader: None,
+            writer: None,
+            last_input_clk: None,
+            last_output_clk: None,
         }
     }
 
-    pub fn set_code(&mut self, code: Vec<Fr>) {
-        self.code = code.clone();
-        let mut program = vec![];
-        for i in 0..code.len() {
-            if i > 0 && (code[i - 1] == ('[' as u64).into() || code[i - 1] == (']' as u64).into()) {
-                let ip = code[i].get_lower_128() as usize;
-                let current_instruction = if ip == code.len() {
-                    Fr::from(0)
-                } else {
-                    code[ip]
-                };
-
-                let next_instruction = if ip + 1 >= code.len() {
-                    Fr::from(0)
-                } else {
-                    code[ip + 1]
-                };
-
-                program.push(InstructionMatrixRow {
-                    instruction_pointer: code[i],
-                    current_instruction,
-                    next_instruction,
-                });
-                continue;
-            }
-            let next_instruction = if i == code.len() - 1 {
-                Fr::from(0)
-            } else {
-                code[i + 1]
-            };
-            program.push(InstructionMatrixRow {
-                instruction_pointer: Fr::from(i as u64),
-                current_instruction: code[i],
-                next_instruction,
-            });
-        }
+    pub fn builder(code: Vec<Instruction>) -> InterpreterBuilder {
+        InterpreterBuilder::new(code)
+    }
 
-        self.matrix.program = program
+    pub fn set_code(&mut self, code: Vec<Instruction>) {
+        self.matrix.program = program_rows(&code);
+        self.code = code;
     }
 
     pub fn set_input(&mut self, input: Vec<Fr>) {
         self.input = input;
     }
 
+    /// Reads the input from `reader` as `,` needs it, once the values given
+    /// to [`Interpreter::set_input`] are used up.
+    pub fn set_reader(&mut self, reader: impl ByteSource + 'static) {
+        self.reader = Some(Box::new(reader));
+    }
+
+    /// Writes the output to `writer` instead of [`Interpreter::output`]. `.`
+    /// writes the low byte of the cell.
+    pub fn set_writer(&mut self, writer: impl ByteSink + 'static) {
+        self.writer = Some(Box::new(writer));
+    }
+
     pub fn set_bits(&mut self, bits: u64) {
         self.bits = bits
     }
 
-    pub fn run(&mut self) {
-        self.register.current_instruction = self.code[0];
-        if self.code.len() == 1 {
-            self.register.next_instruction = Fr::zero()
+    pub fn set_eof(&mut self, eof: EofPolicy) {
+        self.eof = eof
+    }
+
+    /// Initial tape image, cells past its end start at zero.
+    pub fn set_tape(&mut self, tape: Vec<Fr>) {
+        self.memory = if tape.is_empty() {
+            vec![Fr::zero()]
         } else {
-            self.register.next_instruction = self.code[1];
+            tape
+        };
+    }
+
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic
+    }
+
+    /// Prepares the registers and the program rows of the instruction matrix.
+    /// Must be called once before the first [`Interpreter::step`].
+    pub fn init(&mut self) {
+        self.with_matrix(|vm, matrix| vm.init_into(matrix));
+    }
+
+    /// Executes the current instruction and records its trace rows.
+    /// Panics on a [`Trap`], see [`Interpreter::try_step`].
+    pub fn step(&mut self) {
+        self.with_matrix(|vm, matrix| vm.step_into(matrix));
+    }
+
+    pub fn try_step(&mut self) -> Result<(), Trap> {
+        let mut result = Ok(());
+        self.with_matrix(|vm, matrix| result = vm.try_step_into(matrix));
+        result
+    }
+
+    /// Records the halting row and sorts the instruction and memory matrices.
+    pub fn finalize(&mut self) {
+        self.with_matrix(|vm, matrix| vm.finalize_into(matrix));
+    }
+
+    /// Panics on a [`Trap`], see [`Interpreter::try_run`].
+    pub fn run(&mut self) {
+        self.with_matrix(|vm, matrix| vm.run_into(matrix));
+    }
+
+    /// Runs the program, or stops at the first [`Trap`]. The matrix of a
+    /// trapped run is not finalized and cannot be proven.
+    pub fn try_run(&mut self) -> Result<(), Trap> {
+        let mut result = Ok(());
+        self.with_matrix(|vm, matrix| result = vm.try_run_into(matrix));
+        result
+    }
+
+    fn with_matrix(&mut self, f: impl FnOnce(&mut Self, &mut Matrix)) {
+        let mut matrix = core::mem::take(&mut self.matrix);
+        f(self, &mut matrix);
+        self.matrix = matrix;
+    }
+
+    /// Runs the program, streaming its trace into `sink` instead of `matrix`.
+    pub fn run_into(&mut self, sink: &mut impl TraceSink) {
+        self.try_run_into(sink)
+            .unwrap_or_else(|trap| panic!("{}", trap));
+    }
+
+    pub fn try_run_into(&mut self, sink: &mut impl TraceSink) -> Result<(), Trap> {
+        self.try_run_traced(&mut Recorder(sink))
+    }
+
+    /// Runs the program, reporting it to `tracer` only. Panics on a [`Trap`].
+    pub fn run_traced(&mut self, tracer: &mut impl Tracer) {
+        self.try_run_traced(tracer)
+            .unwrap_or_else(|trap| panic!("{}", trap));
+    }
+
+    pub fn try_run_traced(&mut self, tracer: &mut impl Tracer) -> Result<(), Trap> {
+        self.init_traced(tracer);
+        while !self.is_halted() {
+            self.try_step_traced(tracer)?;
         }
-        for i in 0..self.code.len() {
-            self.matrix.instruction_matrix.push(InstructionMatrixRow {
-                instruction_pointer: Fr::from(i as u64),
-                current_instruction: self.code[i],
-                next_instruction: if i == self.code.len() - 1 {
-                    Fr::zero()
-                } else {
-                    self.code[i + 1]
-                },
-            });
+        self.finalize_traced(tracer);
+        if let Some(writer) = &mut self.writer {
+            writer.flush().map_err(|_| Trap::Io {
+                cycle: self.register.cycle.get_lower_128() as u64,
+                ip: self.register.ip(),
+            })?;
         }
-        loop {
-            if self.register.instruction_pointer >= Fr::from(self.code.len() as u64) {
-                break;
+        Ok(())
+    }
+
+    pub fn init_into(&mut self, sink: &mut impl TraceSink) {
+        self.init_traced(&mut Recorder(sink));
+    }
+
+    pub fn init_traced(&mut self, tracer: &mut impl Tracer) {
+        self.load_instruction();
+        self.load_memory_value();
+        tracer.on_start(&self.code);
+    }
+
+    // current/next instruction and operand registers at the current ip
+    fn load_instruction(&mut self) {
+        let ip = self.register.ip();
+        self.register.current_instruction = opcode(&self.code, ip);
+        self.register.next_instruction = opcode(&self.code, ip + 1);
+        self.register.operand = operand(&self.code, ip);
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.register.instruction_pointer >= Fr::from(self.code.len() as u64)
+    }
+
+    pub fn step_into(&mut self, sink: &mut impl TraceSink) {
+        self.try_step_into(sink)
+            .unwrap_or_else(|trap| panic!("{}", trap));
+    }
+
+    // the trap, if the current instruction leaves the tape or the cell range
+    fn check_trap(&self) -> Option<Trap> {
+        let (cycle, ip, mp) = (
+            self.register.cycle.get_lower_128() as u64,
+            self.register.ip(),
+            self.register.mp(),
+        );
+        let mv = self.memory[mp];
+        let strict = self.arithmetic == Arithmetic::Strict;
+        match self.register.current_instruction.get_lower_128() as u8 {
+            code::SHL if mp == 0 => Some(Trap::TapeUnderflow { cycle, ip }),
+            code::ADD if strict && mv == Fr::from((1 << self.bits) - 1) => {
+                Some(Trap::Overflow { cycle, ip, mp })
             }
-            self.matrix.processor_matrix.push(self.register.clone());
-            self.matrix
-                .instruction_matrix
-                .push(InstructionMatrixRow::from(&self.register));
-            self.matrix
-                .memory_matrix
-                .push(MemoryMatrixRow::from(&self.register));
-            match self.register.current_instruction.get_lower_128() as u8 {
-                code::SHL => {
-                    self.register.memory_pointer -= Fr::one();
-                    self.register.instruction_pointer += Fr::one();
-                }
-                code::SHR => {
-                    self.register.memory_pointer += Fr::one();
-                    if self.register.mp() == self.memory.len() {
-                        self.memory.push(Fr::zero())
+            code::SUB if strict && mv == Fr::zero() => Some(Trap::Underflow { cycle, ip, mp }),
+            _ => None,
+        }
+    }
+
+    // reads the byte of a `,` and writes the byte of a `.`, setting the eof
+    // flag at the first `,` without input
+    fn transfer(&mut self) -> Result<(), IoError> {
+        match self.register.current_instruction.get_lower_128() as u8 {
+            code::GETCHAR if self.input.is_empty() && self.register.eof == Fr::zero() => {
+                if let Some(reader) = &mut self.reader {
+                    if let Some(byte) = reader.read_byte()? {
+                        self.input.push(Fr::from(byte as u64));
                     }
-                    self.register.instruction_pointer += Fr::one();
                 }
-                code::ADD => {
-                    if self.memory[self.register.mp()] == Fr::from((1 << self.bits) - 1) {
-                        self.memory[self.register.mp()] = Fr::zero()
-                    } else {
-                        self.memory[self.register.mp()] =
-                            self.memory[self.register.mp()] + Fr::one();
-                    }
-                    self.register.instruction_pointer += Fr::one();
+                if self.input.is_empty() {
+                    self.register.eof = Fr::one();
                 }
-                code::SUB => {
-                    if self.memory[self.register.mp()] == Fr::zero() {
-                        self.memory[self.register.mp()] = Fr::from((1 << self.bits) - 1)
-                    } else {
-                        self.memory[self.register.mp()] =
-                            self.memory[self.register.mp()] - Fr::one();
-                    }
-                    self.register.instruction_pointer += Fr::one();
+            }
+            code::PUTCHAR => {
+                let byte = self.register.memory_value.get_lower_128() as u8;
+                match &mut self.writer {
+                    Some(writer) => writer.write_byte(byte)?,
+                    None => self.output.push(byte),
                 }
-                code::GETCHAR => {
-                    let val = self.input.remove(0);
-                    self.memory[self.register.mp()] = val;
-
-                    let last_clk = match self.matrix.input_matrix.last() {
-                        Some(m) => m.cycle + Fr::one(),
-                        None => self.register.cycle,
-                    };
+            }
+            _ => {}
+        }
+        Ok(())
+    }
 
-                    self.matrix.input_matrix.push(IOMatrixRow {
-                        cycle: self.register.cycle + Fr::one(),
-                        value: val,
-                        diff: self.register.cycle - last_clk,
-                    });
+    pub fn try_step_into(&mut self, sink: &mut impl TraceSink) -> Result<(), Trap> {
+        self.try_step_traced(&mut Recorder(sink))
+    }
 
-                    self.register.instruction_pointer += Fr::one();
-                }
-                code::PUTCHAR => {
-                    let last_clk = match self.matrix.output_matrix.last() {
-                        Some(m) => m.cycle + Fr::one(),
-                        None => self.register.cycle,
-                    };
-
-                    self.matrix.output_matrix.push(IOMatrixRow {
-                        cycle: self.register.cycle,
-                        value: self.register.memory_value,
-                        diff: self.register.cycle - last_clk,
-                    });
-                    self.register.instruction_pointer += Fr::one();
+    pub fn try_step_traced(&mut self, tracer: &mut impl Tracer) -> Result<(), Trap> {
+        if let Some(trap) = self.check_trap() {
+            return Err(trap);
+        }
+        self.transfer().map_err(|_| Trap::Io {
+            cycle: self.register.cycle.get_lower_128() as u64,
+            ip: self.register.ip(),
+        })?;
+        tracer.on_step(&self.register);
+        let (opcode, mp) = (
+            self.register.current_instruction.get_lower_128() as u8,
+            self.register.mp(),
+        );
+        let old = self.memory[mp];
+        match opcode {
+            code::SHL => {
+                self.register.memory_pointer -= Fr::one();
+                self.register.instruction_pointer += Fr::one();
+            }
+            code::SHR => {
+                self.register.memory_pointer += Fr::one();
+                if self.register.mp() == self.memory.len() {
+                    self.memory.push(Fr::zero())
                 }
-                code::LB => {
-                    if self.memory[self.register.mp()] == Fr::zero() {
-                        self.register.instruction_pointer = self.code[self.register.ip() + 1];
-                    } else {
-                        self.register.instruction_pointer += Fr::from(2);
-                    }
+                self.register.instruction_pointer += Fr::one();
+            }
+            code::ADD => {
+                if self.memory[self.register.mp()] == Fr::from((1 << self.bits) - 1) {
+                    self.memory[self.register.mp()] = Fr::zero()
+                } else {
+                    self.memory[self.register.mp()] = self.memory[self.register.mp()] + Fr::one();
                 }
-                code::RB => {
-                    if self.memory[self.register.mp()] != Fr::zero() {
-                        self.register.instruction_pointer = self.code[self.register.ip() + 1];
-                    } else {
-                        self.register.instruction_pointer += Fr::from(2);
-                    }
+                self.register.instruction_pointer += Fr::one();
+            }
+            code::SUB => {
+                if self.memory[self.register.mp()] == Fr::zero() {
+                    self.memory[self.register.mp()] = Fr::from((1 << self.bits) - 1)
+                } else {
+                    self.memory[self.register.mp()] = self.memory[self.register.mp()] - Fr::one();
                 }
-                _ => unreachable!(),
+                self.register.instruction_pointer += Fr::one();
             }
-            self.register.cycle += Fr::one();
-            if self.register.instruction_pointer < Fr::from(self.code.len() as u64) {
-                self.register.current_instruction = self.code[self.register.ip()];
-            } else {
-                self.register.current_instruction = Fr::zero();
+            code::GETCHAR if self.register.eof == Fr::one() => {
+                let mv = self.memory[self.register.mp()];
+                self.memory[self.register.mp()] = self.eof.value(mv, self.bits);
+                self.register.instruction_pointer += Fr::one();
+            }
+            code::GETCHAR => {
+                let val = self.input.remove(0);
+                self.memory[self.register.mp()] = val;
+
+                let last_clk = match self.last_input_clk {
+                    Some(clk) => clk + Fr::one(),
+                    None => self.register.cycle,
+                };
+                self.last_input_clk = Some(self.register.cycle + Fr::one());
+
+                tracer.on_input(&IOMatrixRow {
+                    cycle: self.register.cycle + Fr::one(),
+                    value: val,
+                    diff: self.register.cycle - last_clk,
+                });
+
+                self.register.instruction_pointer += Fr::one();
             }
-            if self.register.instruction_pointer < Fr::from(self.code.len() as u64) - Fr::one() {
-                self.register.next_instruction = self.code[self.register.ip() + 1];
-            } else {
-                self.register.next_instruction = Fr::zero()
+            code::PUTCHAR => {
+                let last_clk = match self.last_output_clk {
+                    Some(clk) => clk + Fr::one(),
+                    None => self.register.cycle,
+                };
+                self.last_output_clk = Some(self.register.cycle);
+
+                tracer.on_output(&IOMatrixRow {
+                    cycle: self.register.cycle,
+                    value: self.register.memory_value,
+                    diff: self.register.cycle - last_clk,
+                });
+                self.register.instruction_pointer += Fr::one();
+            }
+            code::LB => {
+                if self.memory[self.register.mp()] == Fr::zero() {
+                    self.register.instruction_pointer = self.register.operand;
+                } else {
+                    self.register.instruction_pointer += Fr::one();
+                }
+            }
+            code::RB => {
+                if self.memory[self.register.mp()] != Fr::zero() {
+                    self.register.instruction_pointer = self.register.operand;
+                } else {
+                    self.register.instruction_pointer += Fr::one();
+                }
             }
-            self.register.memory_value = self.memory[self.register.mp()];
-            self.register.memory_value_inverse = if self.register.memory_value == Fr::zero() {
-                Fr::zero()
-            } else {
-                self.register.memory_value.invert().unwrap()
-            };
+            _ => unreachable!(),
         }
-        self.matrix.processor_matrix.push(self.register.clone());
-        self.matrix
-            .memory_matrix
-            .push(MemoryMatrixRow::from(&self.register));
-        self.matrix
-            .instruction_matrix
-            .push(InstructionMatrixRow::from(&self.register));
-        self.matrix
-            .instruction_matrix
-            .sort_by_key(|row| row.instruction_pointer);
-        self.matrix
-            .memory_matrix
-            .sort_by_key(|row| row.memory_pointer);
+        if matches!(opcode, code::ADD | code::SUB | code::GETCHAR) {
+            tracer.on_memory_write(mp, old, self.memory[mp]);
+        }
+        self.register.cycle += Fr::one();
+        self.load_instruction();
+        self.load_memory_value();
+        Ok(())
+    }
+
+    fn load_memory_value(&mut self) {
+        self.register.memory_value = self.memory[self.register.mp()];
+        self.register.memory_value_inverse = if self.register.memory_value == Fr::zero() {
+            Fr::zero()
+        } else {
+            self.register.memory_value.invert().unwrap()
+        };
+    }
+
+    pub fn finalize_into(&mut self, sink: &mut impl TraceSink) {
+        self.finalize_traced(&mut Recorder(sink));
 
         // Append dummy memory rows
         // let mut i = 1;
@@ -271,4 +560,133 @@ impl Interpreter {
         //     i += 1;
         // }
     }
+
+    pub fn finalize_traced(&mut self, tracer: &mut impl Tracer) {
+        tracer.on_halt(&self.register);
+    }
+}
+
+/// Why [`InterpreterBuilder::run`] stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The program ran to completion, the trace can be proven.
+    Halted,
+    /// The step limit was reached first.
+    StepLimit,
+    Trap(Trap),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunResult {
+    pub output: Vec<u8>,
+    /// The same matrix as `Interpreter::run` builds. It is only finalized
+    /// when the program [`HaltReason::Halted`].
+    pub trace: Matrix,
+    pub cycles: u64,
+    /// Highest memory pointer reached.
+    pub max_mp: usize,
+    pub halted_reason: HaltReason,
+}
+
+/// Configuration of a run. Every [`InterpreterBuilder::run`] starts from a
+/// fresh [`Interpreter`], so one builder can run a program many times.
+#[derive(Clone, Debug)]
+pub struct InterpreterBuilder {
+    code: Vec<Instruction>,
+    input: Vec<Fr>,
+    tape: Vec<Fr>,
+    bits: u64,
+    eof: EofPolicy,
+    arithmetic: Arithmetic,
+    max_cycles: Option<u64>,
+}
+
+impl InterpreterBuilder {
+    pub fn new(code: Vec<Instruction>) -> Self {
+        Self {
+            code,
+            input: Vec::new(),
+            tape: Vec::new(),
+            bits: 8,
+            eof: EofPolicy::default(),
+            arithmetic: Arithmetic::default(),
+            max_cycles: None,
+        }
+    }
+
+    pub fn input(mut self, input: Vec<Fr>) -> Self {
+        self.input = input;
+        self
+    }
+
+    pub fn tape(mut self, tape: Vec<Fr>) -> Self {
+        self.tape = tape;
+        self
+    }
+
+    pub fn bits(mut self, bits: u64) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    pub fn eof(mut self, eof: EofPolicy) -> Self {
+        self.eof = eof;
+        self
+    }
+
+    pub fn arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// Stops the run after `max_cycles` instructions.
+    pub fn max_cycles(mut self, max_cycles: u64) -> Self {
+        self.max_cycles = Some(max_cycles);
+        self
+    }
+
+    pub fn build(&self) -> Interpreter {
+        let mut vm = Interpreter::new();
+        vm.set_code(self.code.clone());
+        vm.set_input(self.input.clone());
+        vm.set_tape(self.tape.clone());
+        vm.set_bits(self.bits);
+        vm.set_eof(self.eof);
+        vm.set_arithmetic(self.arithmetic);
+        vm
+    }
+
+    pub fn run(&self) -> RunResult {
+        self.run_with(&mut ())
+    }
+
+    /// Runs the program, reporting it to `tracer` as well.
+    pub fn run_with(&self, tracer: &mut impl Tracer) -> RunResult {
+        let mut vm = self.build();
+        let mut trace = core::mem::take(&mut vm.matrix);
+        let mut tracer = (Recorder(&mut trace), tracer);
+        vm.init_traced(&mut tracer);
+        let mut max_mp = 0;
+        let halted_reason = loop {
+            max_mp = max_mp.max(vm.register.mp());
+            let cycles = vm.register.cycle.get_lower_128() as u64;
+            if vm.is_halted() {
+                vm.finalize_traced(&mut tracer);
+                break HaltReason::Halted;
+            }
+            if matches!(self.max_cycles, Some(max) if cycles >= max) {
+                break HaltReason::StepLimit;
+            }
+            if let Err(trap) = vm.try_step_traced(&mut tracer) {
+                break HaltReason::Trap(trap);
+            }
+        };
+        RunResult {
+            output: vm.output,
+            trace,
+            cycles: vm.register.cycle.get_lower_128() as u64,
+            max_mp,
+            halted_reason,
+        }
+    }
 }
@@ -0,0 +1,78 @@
+//! Byte sources and sinks for `,` and `.`, so the interpreter can read its
+//! input from and write its output to streams. Without the `std` feature,
+//! implement [`ByteSource`] and [`ByteSink`] for the device at hand; with it,
+//! any `std::io::Read` or `Write` works through [`Reader`] and [`Writer`].
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A failed read or write. The underlying error is dropped so that the
+/// traits do not depend on `std::io`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoError;
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "I/O error")
+    }
+}
+
+pub trait ByteSource {
+    /// The next input byte, or `None` once the input is exhausted.
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError>;
+}
+
+pub trait ByteSink {
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError>;
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = u8>> ByteSource for I {
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError> {
+        Ok(self.next())
+    }
+}
+
+impl ByteSink for Vec<u8> {
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError> {
+        self.push(byte);
+        Ok(())
+    }
+}
+
+/// [`ByteSource`] over a `std::io::Read`, e.g. `Reader(std::io::stdin())`.
+/// Reads one byte at a time, so wrap files in a `BufReader`.
+#[cfg(feature = "std")]
+pub struct Reader<R>(pub R);
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> ByteSource for Reader<R> {
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError> {
+        let mut byte = [0];
+        loop {
+            match self.0.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => return Err(IoError),
+            }
+        }
+    }
+}
+
+/// [`ByteSink`] over a `std::io::Write`, e.g. `Writer(std::io::stdout())`.
+#[cfg(feature = "std")]
+pub struct Writer<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> ByteSink for Writer<W> {
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError> {
+        self.0.write_all(&[byte]).map_err(|_| IoError)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.0.flush().map_err(|_| IoError)
+    }
+}
@@ -2,9 +2,23 @@
 
 #[macro_use]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+pub mod analyze;
+pub mod asm;
 pub mod code;
+pub mod debugger;
+pub mod dump;
 pub mod interpreter;
+pub mod io;
 pub mod matrix;
+pub mod native;
+pub mod profile;
+pub mod sink;
+pub mod sizing;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod tracer;
 
 // mod tests;
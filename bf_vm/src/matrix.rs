@@ -5,7 +5,7 @@ use crate::interpreter::Register;
 use core::convert::From;
 use halo2_proofs::halo2curves::bn256::Fr;
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Matrix {
     pub processor_matrix: Vec<Register>,
     pub instruction_matrix: Vec<InstructionMatrixRow>,
@@ -15,11 +15,39 @@ pub struct Matrix {
     pub program: Vec<InstructionMatrixRow>,
 }
 
-#[derive(Debug, Clone)]
+impl Matrix {
+    /// Value of every cell the program touched when it first accessed it,
+    /// read from the memory matrix sorted by (mp, clk).
+    pub fn initial_tape(&self) -> Vec<Fr> {
+        let rows = &self.memory_matrix;
+        rows.iter()
+            .enumerate()
+            .filter(|(i, row)| *i == 0 || rows[i - 1].memory_pointer != row.memory_pointer)
+            .map(|(_, row)| row.memory_value)
+            .collect()
+    }
+
+    /// Value of every cell the program touched once it halted. Only `<` and
+    /// `>` leave a cell and they do not change it, so the last row of a cell
+    /// holds its final value.
+    pub fn final_tape(&self) -> Vec<Fr> {
+        let rows = &self.memory_matrix;
+        rows.iter()
+            .enumerate()
+            .filter(|(i, row)| {
+                *i + 1 == rows.len() || rows[i + 1].memory_pointer != row.memory_pointer
+            })
+            .map(|(_, row)| row.memory_value)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InstructionMatrixRow {
     pub instruction_pointer: Fr,
     pub current_instruction: Fr,
     pub next_instruction: Fr,
+    pub operand: Fr,
 }
 
 impl From<&Register> for InstructionMatrixRow {
@@ -28,10 +56,12 @@ impl From<&Register> for InstructionMatrixRow {
             instruction_pointer: r.instruction_pointer,
             current_instruction: r.current_instruction,
             next_instruction: r.next_instruction,
+            operand: r.operand,
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MemoryMatrixRow {
     pub cycle: Fr,
     pub memory_pointer: Fr,
@@ -50,7 +80,7 @@ impl From<&Register> for MemoryMatrixRow {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IOMatrixRow {
     pub cycle: Fr,
     pub value: Fr,
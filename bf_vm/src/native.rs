@@ -0,0 +1,299 @@
+use crate::code::{self, Instruction};
+use crate::interpreter::{program_rows, Arithmetic, EofPolicy, Register, Trap};
+use crate::matrix::{IOMatrixRow, InstructionMatrixRow, Matrix, MemoryMatrixRow};
+use crate::sink::TraceSink;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use halo2_proofs::arithmetic::Field;
+use halo2_proofs::halo2curves::bn256::Fr;
+
+/// Tape cell of the native interpreter. Arithmetic wraps modulo `2^BITS`,
+/// matching `Interpreter` with `set_bits(BITS)`; under
+/// [`Arithmetic::Strict`] the interpreter traps before wrapping.
+pub trait Cell: Copy + Default + Eq {
+    const BITS: u64;
+    fn inc(self) -> Self;
+    fn dec(self) -> Self;
+    fn from_byte(b: u8) -> Self;
+    fn to_u64(self) -> u64;
+}
+
+macro_rules! impl_cell {
+    ($t:ty) => {
+        impl Cell for $t {
+            const BITS: u64 = <$t>::BITS as u64;
+            fn inc(self) -> Self {
+                self.wrapping_add(1)
+            }
+            fn dec(self) -> Self {
+                self.wrapping_sub(1)
+            }
+            fn from_byte(b: u8) -> Self {
+                b as $t
+            }
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+        }
+    };
+}
+
+impl_cell!(u8);
+impl_cell!(u16);
+impl_cell!(u32);
+
+/// Interpreter over native integers for plain execution. It runs the same
+/// compiled instructions as `Interpreter` (see [`code::compile`]) but never
+/// touches field arithmetic unless a trace is requested with
+/// [`NativeInterpreter::trace`].
+pub struct NativeInterpreter<C: Cell = u8> {
+    pub code: Vec<Instruction>,
+    pub input: Vec<C>,
+    pub tape: Vec<C>,
+    pub memory: Vec<C>,
+    pub ip: usize,
+    pub mp: usize,
+    pub cycles: u64,
+    pub eof: EofPolicy,
+    pub arithmetic: Arithmetic,
+    input_pos: usize,
+    // set by the first `,` without input, like the `eof` register
+    at_eof: bool,
+    // cells take few distinct values, so inverses for the trace are memoized
+    inverses: BTreeMap<u64, Fr>,
+}
+
+impl<C: Cell> NativeInterpreter<C> {
+    pub fn new(code: Vec<Instruction>) -> Self {
+        Self {
+            code,
+            input: Vec::new(),
+            tape: Vec::new(),
+            memory: vec![C::default()],
+            ip: 0,
+            mp: 0,
+            cycles: 0,
+            eof: EofPolicy::default(),
+            arithmetic: Arithmetic::default(),
+            input_pos: 0,
+            at_eof: false,
+            inverses: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_input(&mut self, input: &[u8]) {
+        self.input = input.iter().map(|&b| C::from_byte(b)).collect();
+    }
+
+    /// Initial tape image, cells past its end start at zero.
+    pub fn set_tape(&mut self, tape: &[u8]) {
+        self.tape = tape.iter().map(|&b| C::from_byte(b)).collect();
+    }
+
+    pub fn set_eof(&mut self, eof: EofPolicy) {
+        self.eof = eof;
+    }
+
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    fn reset(&mut self) {
+        self.memory = if self.tape.is_empty() {
+            vec![C::default()]
+        } else {
+            self.tape.clone()
+        };
+        self.ip = 0;
+        self.mp = 0;
+        self.cycles = 0;
+        self.input_pos = 0;
+        self.at_eof = false;
+    }
+
+    /// Runs the program from the start and returns its output. Panics on a
+    /// [`Trap`], see [`NativeInterpreter::try_run`].
+    pub fn run(&mut self) -> Vec<C> {
+        self.try_run().unwrap_or_else(|trap| panic!("{}", trap))
+    }
+
+    /// Runs the program from the start, or stops at the first [`Trap`].
+    pub fn try_run(&mut self) -> Result<Vec<C>, Trap> {
+        self.reset();
+        self.execute::<Matrix>(None)
+    }
+
+    /// Runs the program from the start and builds the same `Matrix` as
+    /// `Interpreter::run` does for this program and input.
+    pub fn trace(&mut self) -> (Vec<C>, Matrix) {
+        let mut matrix = Matrix {
+            program: program_rows(&self.code),
+            ..Matrix::default()
+        };
+        let output = self.trace_into(&mut matrix);
+        (output, matrix)
+    }
+
+    /// Runs the program from the start, streaming the execution trace into
+    /// `sink`. Panics on a [`Trap`].
+    pub fn trace_into(&mut self, sink: &mut impl TraceSink) -> Vec<C> {
+        self.reset();
+        for row in program_rows(&self.code) {
+            sink.instruction_row(row);
+        }
+        let output = self
+            .execute(Some(&mut *sink))
+            .unwrap_or_else(|trap| panic!("{}", trap));
+        sink.finish();
+        output
+    }
+
+    fn opcode(&self, ip: usize) -> u64 {
+        self.code.get(ip).map_or(0, |i| i.opcode as u64)
+    }
+
+    fn register(&mut self) -> Register {
+        let value = self.memory[self.mp].to_u64();
+        let mv = Fr::from(value);
+        let inverse = *self
+            .inverses
+            .entry(value)
+            .or_insert_with(|| mv.invert().unwrap_or(Fr::zero()));
+        Register {
+            cycle: Fr::from(self.cycles),
+            instruction_pointer: Fr::from(self.ip as u64),
+            current_instruction: Fr::from(self.opcode(self.ip)),
+            next_instruction: Fr::from(self.opcode(self.ip + 1)),
+            operand: Fr::from(self.code.get(self.ip).map_or(0, |i| i.operand as u64)),
+            memory_pointer: Fr::from(self.mp as u64),
+            memory_value: mv,
+            memory_value_inverse: inverse,
+            eof: Fr::from(self.at_eof as u64),
+        }
+    }
+
+    // the trap, if the current instruction cannot execute
+    fn check_trap(&self) -> Option<Trap> {
+        let (cycle, ip, mp) = (self.cycles, self.ip, self.mp);
+        let mv = self.memory[mp];
+        let strict = self.arithmetic == Arithmetic::Strict;
+        match self.code[ip].opcode {
+            code::SHL if mp == 0 => Some(Trap::TapeUnderflow { cycle, ip }),
+            code::ADD if strict && mv == C::default().dec() => {
+                Some(Trap::Overflow { cycle, ip, mp })
+            }
+            code::SUB if strict && mv == C::default() => Some(Trap::Underflow { cycle, ip, mp }),
+            _ => None,
+        }
+    }
+
+    fn execute<S: TraceSink>(&mut self, mut trace: Option<&mut S>) -> Result<Vec<C>, Trap> {
+        let mut output = Vec::new();
+        let mut last_input_clk = None;
+        let mut last_output_clk = None;
+        while self.ip < self.code.len() {
+            if let Some(trap) = self.check_trap() {
+                return Err(trap);
+            }
+            if self.code[self.ip].opcode == code::GETCHAR && self.input_pos == self.input.len() {
+                self.at_eof = true;
+            }
+            if let Some(sink) = trace.as_deref_mut() {
+                let register = self.register();
+                sink.instruction_row(InstructionMatrixRow::from(&register));
+                sink.memory_row(MemoryMatrixRow::from(&register));
+                sink.processor_row(register);
+            }
+            let instruction = self.code[self.ip];
+            match instruction.opcode {
+                code::SHL => {
+                    self.mp -= 1;
+                    self.ip += 1;
+                }
+                code::SHR => {
+                    self.mp += 1;
+                    if self.mp == self.memory.len() {
+                        self.memory.push(C::default());
+                    }
+                    self.ip += 1;
+                }
+                code::ADD => {
+                    self.memory[self.mp] = self.memory[self.mp].inc();
+                    self.ip += 1;
+                }
+                code::SUB => {
+                    self.memory[self.mp] = self.memory[self.mp].dec();
+                    self.ip += 1;
+                }
+                code::GETCHAR if self.at_eof => {
+                    self.memory[self.mp] = match self.eof {
+                        EofPolicy::Zero => C::default(),
+                        EofPolicy::Max => C::default().dec(),
+                        EofPolicy::Unchanged => self.memory[self.mp],
+                    };
+                    self.ip += 1;
+                }
+                code::GETCHAR => {
+                    let val = self.input[self.input_pos];
+                    self.input_pos += 1;
+                    self.memory[self.mp] = val;
+                    if let Some(sink) = trace.as_deref_mut() {
+                        let cycle = Fr::from(self.cycles);
+                        let last_clk = match last_input_clk {
+                            Some(clk) => clk + Fr::one(),
+                            None => cycle,
+                        };
+                        last_input_clk = Some(cycle + Fr::one());
+                        sink.input_row(IOMatrixRow {
+                            cycle: cycle + Fr::one(),
+                            value: Fr::from(val.to_u64()),
+                            diff: cycle - last_clk,
+                        });
+                    }
+                    self.ip += 1;
+                }
+                code::PUTCHAR => {
+                    let val = self.memory[self.mp];
+                    output.push(val);
+                    if let Some(sink) = trace.as_deref_mut() {
+                        let cycle = Fr::from(self.cycles);
+                        let last_clk = match last_output_clk {
+                            Some(clk) => clk + Fr::one(),
+                            None => cycle,
+                        };
+                        last_output_clk = Some(cycle);
+                        sink.output_row(IOMatrixRow {
+                            cycle,
+                            value: Fr::from(val.to_u64()),
+                            diff: cycle - last_clk,
+                        });
+                    }
+                    self.ip += 1;
+                }
+                code::LB => {
+                    if self.memory[self.mp] == C::default() {
+                        self.ip = instruction.operand as usize;
+                    } else {
+                        self.ip += 1;
+                    }
+                }
+                code::RB => {
+                    if self.memory[self.mp] != C::default() {
+                        self.ip = instruction.operand as usize;
+                    } else {
+                        self.ip += 1;
+                    }
+                }
+                _ => unreachable!(),
+            }
+            self.cycles += 1;
+        }
+        if let Some(sink) = trace {
+            let register = self.register();
+            sink.memory_row(MemoryMatrixRow::from(&register));
+            sink.instruction_row(InstructionMatrixRow::from(&register));
+            sink.processor_row(register);
+        }
+        Ok(output)
+    }
+}
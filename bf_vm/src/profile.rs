@@ -0,0 +1,221 @@
+//! Attributes the cycles of a run to instructions and loops. The prover pays
+//! for every cycle, so this is where to look before shrinking a program.
+use crate::code::{self, Instruction};
+use crate::interpreter::Register;
+use crate::sizing::k_for;
+use crate::tracer::Tracer;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// One `[...]` loop of the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoopProfile {
+    /// Instruction pointers of the `[` and of the matching `]`.
+    pub start: usize,
+    pub end: usize,
+    /// Number of enclosing loops.
+    pub depth: usize,
+    /// Times the `[` was reached, whether or not the body ran.
+    pub entries: u64,
+    pub iterations: u64,
+    /// Cycles spent from the `[` to the `]`, nested loops included.
+    pub cycles: u64,
+    /// `cycles` minus those of the nested loops.
+    pub self_cycles: u64,
+    /// `k` of a circuit proving these cycles alone.
+    pub k: u32,
+}
+
+/// [`Tracer`] counting the cycles spent on every instruction.
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    code: Vec<Instruction>,
+    source_map: Vec<usize>,
+    counts: Vec<u64>,
+    bits: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            code: Vec::new(),
+            source_map: Vec::new(),
+            counts: Vec::new(),
+            bits: 8,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attaches the map produced by [`crate::code::compile_with_source_map`],
+    /// so that positions are reported as source offsets instead of
+    /// instruction pointers.
+    pub fn with_source_map(mut self, source_map: Vec<usize>) -> Self {
+        self.source_map = source_map;
+        self
+    }
+
+    /// Cell bits of the run, which set the smallest `k` of the circuit.
+    pub fn with_bits(mut self, bits: u64) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    /// Cycles of the run so far.
+    pub fn cycles(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Cycles spent on each instruction, by instruction pointer.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// `k` of the circuit proving the whole run.
+    pub fn k(&self) -> u32 {
+        k_for(self.code.len(), self.cycles(), self.bits)
+    }
+
+    /// Source offset of the instruction at `ip`, or `ip` itself without a
+    /// source map.
+    pub fn position(&self, ip: usize) -> usize {
+        self.source_map.get(ip).copied().unwrap_or(ip)
+    }
+
+    // cycles of the instructions in start..=end
+    fn span(&self, start: usize, end: usize) -> u64 {
+        self.counts[start..=end].iter().sum()
+    }
+
+    /// Every loop of the program, in source order.
+    pub fn loops(&self) -> Vec<LoopProfile> {
+        let mut loops = Vec::new();
+        // index in `loops` of the open loops
+        let mut open = Vec::<usize>::new();
+        for (ip, i) in self.code.iter().enumerate() {
+            if i.opcode == code::LB {
+                let end = i.operand as usize - 1;
+                let cycles = self.span(ip, end);
+                loops.push(LoopProfile {
+                    start: ip,
+                    end,
+                    depth: open.len(),
+                    entries: self.counts[ip],
+                    // the instruction after `[` runs once per iteration
+                    iterations: self.counts[ip + 1],
+                    cycles,
+                    self_cycles: cycles,
+                    k: k_for(self.code.len(), cycles, self.bits),
+                });
+                if let Some(&parent) = open.last() {
+                    loops[parent].self_cycles -= cycles;
+                }
+                open.push(loops.len() - 1);
+            } else if i.opcode == code::RB {
+                open.pop();
+            }
+        }
+        loops
+    }
+
+    /// Stacks of loops in the folded format of flamegraph tools, one line
+    /// per stack with its self cycles, e.g. `program;loop@3;loop@8 120`.
+    pub fn folded(&self) -> String {
+        let mut stacks = BTreeMap::<String, u64>::new();
+        let mut frames = vec![String::from("program")];
+        let mut self_cycles = vec![0];
+        for (ip, i) in self.code.iter().enumerate() {
+            if i.opcode == code::LB {
+                frames.push(format!("loop@{}", self.position(ip)));
+                self_cycles.push(0);
+            }
+            *self_cycles.last_mut().unwrap() += self.counts[ip];
+            if i.opcode == code::RB {
+                let cycles = self_cycles.pop().unwrap();
+                *stacks.entry(frames.join(";")).or_default() += cycles;
+                frames.pop();
+            }
+        }
+        *stacks.entry(frames.join(";")).or_default() += self_cycles[0];
+
+        let mut folded = String::new();
+        for (stack, cycles) in stacks.into_iter().filter(|(_, c)| *c > 0) {
+            let _ = writeln!(folded, "{} {}", stack, cycles);
+        }
+        folded
+    }
+
+    /// Text report of the `top` hottest loops and instructions.
+    pub fn report(&self, top: usize) -> String {
+        let cycles = self.cycles();
+        let percent = |c: u64| c as f64 * 100.0 / cycles.max(1) as f64;
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "{} cycles over {} instructions, k = {}",
+            cycles,
+            self.code.len(),
+            self.k()
+        );
+
+        let mut loops = self.loops();
+        loops.sort_by_key(|l| core::cmp::Reverse(l.cycles));
+        let _ = writeln!(report, "hot loops:");
+        let _ = writeln!(
+            report,
+            "  {:>8} {:>5} {:>10} {:>12} {:>12} {:>7} {:>12} {:>3}",
+            "pos", "depth", "entries", "iterations", "cycles", "%", "self", "k"
+        );
+        for l in loops.iter().take(top).filter(|l| l.cycles > 0) {
+            let _ = writeln!(
+                report,
+                "  {:>8} {:>5} {:>10} {:>12} {:>12} {:>6.2}% {:>12} {:>3}",
+                self.position(l.start),
+                l.depth,
+                l.entries,
+                l.iterations,
+                l.cycles,
+                percent(l.cycles),
+                l.self_cycles,
+                l.k
+            );
+        }
+
+        let mut hot = (0..self.code.len()).collect::<Vec<_>>();
+        hot.sort_by_key(|&ip| core::cmp::Reverse(self.counts[ip]));
+        let _ = writeln!(report, "hot instructions:");
+        let _ = writeln!(
+            report,
+            "  {:>8} {:>2} {:>12} {:>7}",
+            "pos", "op", "cycles", "%"
+        );
+        for &ip in hot.iter().take(top).filter(|&&ip| self.counts[ip] > 0) {
+            let _ = writeln!(
+                report,
+                "  {:>8} {:>2} {:>12} {:>6.2}%",
+                self.position(ip),
+                self.code[ip].opcode as char,
+                self.counts[ip],
+                percent(self.counts[ip])
+            );
+        }
+        report
+    }
+}
+
+impl Tracer for Profiler {
+    fn on_start(&mut self, code: &[Instruction]) {
+        self.code = code.to_vec();
+        self.counts = vec![0; code.len()];
+    }
+
+    fn on_step(&mut self, register: &Register) {
+        self.counts[register.ip()] += 1;
+    }
+}
@@ -0,0 +1,57 @@
+use crate::interpreter::Register;
+use crate::matrix::{IOMatrixRow, InstructionMatrixRow, Matrix, MemoryMatrixRow};
+
+/// Receives trace rows as they are produced by the interpreters. Rows arrive
+/// in execution order; implementations are responsible for ordering the
+/// instruction table by `instruction_pointer` and the memory table by
+/// `memory_pointer` (stable, so execution order is kept within a key) once
+/// [`TraceSink::finish`] is called.
+///
+/// The program table is not streamed, it is derived from the code with
+/// [`crate::interpreter::program_rows`].
+pub trait TraceSink {
+    fn processor_row(&mut self, row: Register);
+    fn instruction_row(&mut self, row: InstructionMatrixRow);
+    fn memory_row(&mut self, row: MemoryMatrixRow);
+    fn input_row(&mut self, row: IOMatrixRow);
+    fn output_row(&mut self, row: IOMatrixRow);
+    fn finish(&mut self);
+}
+
+impl TraceSink for Matrix {
+    fn processor_row(&mut self, row: Register) {
+        self.processor_matrix.push(row);
+    }
+
+    fn instruction_row(&mut self, row: InstructionMatrixRow) {
+        self.instruction_matrix.push(row);
+    }
+
+    fn memory_row(&mut self, row: MemoryMatrixRow) {
+        self.memory_matrix.push(row);
+    }
+
+    fn input_row(&mut self, row: IOMatrixRow) {
+        self.input_matrix.push(row);
+    }
+
+    fn output_row(&mut self, row: IOMatrixRow) {
+        self.output_matrix.push(row);
+    }
+
+    fn finish(&mut self) {
+        self.instruction_matrix
+            .sort_by_key(|row| row.instruction_pointer);
+        self.memory_matrix.sort_by_key(|row| row.memory_pointer);
+    }
+}
+
+/// Discards the trace, for runs that only want the program's output.
+impl TraceSink for () {
+    fn processor_row(&mut self, _: Register) {}
+    fn instruction_row(&mut self, _: InstructionMatrixRow) {}
+    fn memory_row(&mut self, _: MemoryMatrixRow) {}
+    fn input_row(&mut self, _: IOMatrixRow) {}
+    fn output_row(&mut self, _: IOMatrixRow) {}
+    fn finish(&mut self) {}
+}
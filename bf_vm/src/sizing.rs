@@ -0,0 +1,11 @@
+//! Size of the circuit proving a run, shared by the profiler and `bf_zk`'s
+//! `BfCircuitParams::k` so that both agree on the row budget.
+
+/// Smallest `k` of a circuit proving `cycles` cycles of a program of
+/// `program_len` instructions with `bits`-bit cells: the instruction table
+/// holds both, the range table `2^bits` rows, and halo2 reserves a few rows
+/// for blinding.
+pub fn k_for(program_len: usize, cycles: u64, bits: u64) -> u32 {
+    let rows = (program_len + cycles as usize + 1).max(1 << bits) + 16;
+    rows.next_power_of_two().trailing_zeros()
+}
@@ -0,0 +1,362 @@
+//! A [`TraceSink`] that writes the execution trace to disk, so traces of tens
+//! of millions of cycles can be generated with bounded memory. The instruction
+//! and memory tables are sorted with an external merge sort: rows are buffered
+//! in chunks, each chunk is sorted and spilled to a run file, and the runs are
+//! merged into the final table when the sink is finished.
+//!
+//! Every table is stored as a flat file of rows, each row being its field
+//! elements in `to_repr` encoding.
+use crate::interpreter::Register;
+use crate::matrix::{IOMatrixRow, InstructionMatrixRow, Matrix, MemoryMatrixRow};
+use crate::sink::TraceSink;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use halo2_proofs::halo2curves::bn256::Fr;
+use halo2curves::ff::PrimeField;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const PROCESSOR_FILE: &str = "processor.bin";
+pub const INSTRUCTION_FILE: &str = "instruction.bin";
+pub const MEMORY_FILE: &str = "memory.bin";
+pub const INPUT_FILE: &str = "input.bin";
+pub const OUTPUT_FILE: &str = "output.bin";
+
+/// A trace row that can be stored as a fixed number of field elements.
+pub trait Row: Sized {
+    fn fields(&self) -> Vec<Fr>;
+    fn from_fields(fields: &[Fr]) -> Self;
+    const WIDTH: usize;
+
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        for f in self.fields() {
+            w.write_all(f.to_repr().as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Reads the next row, or `None` at the end of the file.
+    fn read_from(r: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut fields = Vec::with_capacity(Self::WIDTH);
+        for i in 0..Self::WIDTH {
+            let mut repr = <Fr as PrimeField>::Repr::default();
+            match r.read_exact(repr.as_mut()) {
+                Err(e) if i == 0 && e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                res => res?,
+            }
+            let f = Option::from(Fr::from_repr(repr)).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "non canonical field element")
+            })?;
+            fields.push(f);
+        }
+        Ok(Some(Self::from_fields(&fields)))
+    }
+}
+
+impl Row for Register {
+    const WIDTH: usize = 9;
+    fn fields(&self) -> Vec<Fr> {
+        vec![
+            self.cycle,
+            self.instruction_pointer,
+            self.current_instruction,
+            self.next_instruction,
+            self.operand,
+            self.memory_pointer,
+            self.memory_value,
+            self.memory_value_inverse,
+            self.eof,
+        ]
+    }
+    fn from_fields(f: &[Fr]) -> Self {
+        Register {
+            cycle: f[0],
+            instruction_pointer: f[1],
+            current_instruction: f[2],
+            next_instruction: f[3],
+            operand: f[4],
+            memory_pointer: f[5],
+            memory_value: f[6],
+            memory_value_inverse: f[7],
+            eof: f[8],
+        }
+    }
+}
+
+impl Row for InstructionMatrixRow {
+    const WIDTH: usize = 4;
+    fn fields(&self) -> Vec<Fr> {
+        vec![
+            self.instruction_pointer,
+            self.current_instruction,
+            self.next_instruction,
+            self.operand,
+        ]
+    }
+    fn from_fields(f: &[Fr]) -> Self {
+        InstructionMatrixRow {
+            instruction_pointer: f[0],
+            current_instruction: f[1],
+            next_instruction: f[2],
+            operand: f[3],
+        }
+    }
+}
+
+impl Row for MemoryMatrixRow {
+    const WIDTH: usize = 4;
+    fn fields(&self) -> Vec<Fr> {
+        vec![
+            self.cycle,
+            self.memory_pointer,
+            self.memory_value,
+            self.interweave_indicator,
+        ]
+    }
+    fn from_fields(f: &[Fr]) -> Self {
+        MemoryMatrixRow {
+            cycle: f[0],
+            memory_pointer: f[1],
+            memory_value: f[2],
+            interweave_indicator: f[3],
+        }
+    }
+}
+
+impl Row for IOMatrixRow {
+    const WIDTH: usize = 3;
+    fn fields(&self) -> Vec<Fr> {
+        vec![self.cycle, self.value, self.diff]
+    }
+    fn from_fields(f: &[Fr]) -> Self {
+        IOMatrixRow {
+            cycle: f[0],
+            value: f[1],
+            diff: f[2],
+        }
+    }
+}
+
+/// Iterates over the rows of a table file.
+pub struct TableReader<R: Row> {
+    reader: BufReader<File>,
+    _row: core::marker::PhantomData<R>,
+}
+
+impl<R: Row> TableReader<R> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            _row: core::marker::PhantomData,
+        })
+    }
+}
+
+impl<R: Row> Iterator for TableReader<R> {
+    type Item = io::Result<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        R::read_from(&mut self.reader).transpose()
+    }
+}
+
+/// Stable external sort of rows by `key`.
+struct ExternalSorter<R: Row> {
+    dir: PathBuf,
+    name: &'static str,
+    key: fn(&R) -> Fr,
+    chunk: Vec<R>,
+    chunk_rows: usize,
+    runs: Vec<PathBuf>,
+}
+
+impl<R: Row> ExternalSorter<R> {
+    fn new(dir: PathBuf, name: &'static str, key: fn(&R) -> Fr, chunk_rows: usize) -> Self {
+        Self {
+            dir,
+            name,
+            key,
+            chunk: Vec::with_capacity(chunk_rows),
+            chunk_rows,
+            runs: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, row: R) -> io::Result<()> {
+        self.chunk.push(row);
+        if self.chunk.len() >= self.chunk_rows {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let path = self
+            .dir
+            .join(format!("{}.run{}", self.name, self.runs.len()));
+        self.write_chunk(&path)?;
+        self.runs.push(path);
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, path: &Path) -> io::Result<()> {
+        let key = self.key;
+        self.chunk.sort_by_key(key);
+        let mut w = BufWriter::new(File::create(path)?);
+        for row in self.chunk.drain(..) {
+            row.write_to(&mut w)?;
+        }
+        w.flush()
+    }
+
+    /// Writes all rows in sorted order to `dir/name`.
+    fn finish(&mut self) -> io::Result<()> {
+        let path = self.dir.join(self.name);
+        if self.runs.is_empty() {
+            return self.write_chunk(&path);
+        }
+        if !self.chunk.is_empty() {
+            self.spill()?;
+        }
+
+        let mut readers = self
+            .runs
+            .iter()
+            .map(TableReader::<R>::open)
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut heads = Vec::with_capacity(readers.len());
+        // ties are broken by run index, earlier runs hold earlier rows
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            let head = reader.next().transpose()?;
+            if let Some(row) = &head {
+                heap.push(Reverse(((self.key)(row), i)));
+            }
+            heads.push(head);
+        }
+
+        let mut w = BufWriter::new(File::create(&path)?);
+        while let Some(Reverse((_, i))) = heap.pop() {
+            let row = heads[i].take().unwrap();
+            row.write_to(&mut w)?;
+            heads[i] = readers[i].next().transpose()?;
+            if let Some(row) = &heads[i] {
+                heap.push(Reverse(((self.key)(row), i)));
+            }
+        }
+        w.flush()?;
+
+        for run in self.runs.drain(..) {
+            fs::remove_file(run)?;
+        }
+        Ok(())
+    }
+}
+
+/// Streams every table of the trace to files in a directory. At most
+/// `chunk_rows` instruction rows and `chunk_rows` memory rows are held in
+/// memory at any time.
+pub struct FileTraceSink {
+    processor: BufWriter<File>,
+    input: BufWriter<File>,
+    output: BufWriter<File>,
+    instruction: ExternalSorter<InstructionMatrixRow>,
+    memory: ExternalSorter<MemoryMatrixRow>,
+    error: Option<io::Error>,
+}
+
+impl FileTraceSink {
+    pub fn create(dir: impl AsRef<Path>, chunk_rows: usize) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let create = |name: &str| File::create(dir.join(name)).map(BufWriter::new);
+        Ok(Self {
+            processor: create(PROCESSOR_FILE)?,
+            input: create(INPUT_FILE)?,
+            output: create(OUTPUT_FILE)?,
+            instruction: ExternalSorter::new(
+                dir.clone(),
+                INSTRUCTION_FILE,
+                |row| row.instruction_pointer,
+                chunk_rows.max(1),
+            ),
+            memory: ExternalSorter::new(
+                dir.clone(),
+                MEMORY_FILE,
+                |row| row.memory_pointer,
+                chunk_rows.max(1),
+            ),
+            error: None,
+        })
+    }
+
+    /// The first I/O error hit while writing, if any. `TraceSink` methods
+    /// cannot fail, so errors are kept here and later rows are dropped.
+    pub fn result(self) -> io::Result<()> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn record(&mut self, f: impl FnOnce(&mut Self) -> io::Result<()>) {
+        if self.error.is_none() {
+            if let Err(e) = f(self) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+impl TraceSink for FileTraceSink {
+    fn processor_row(&mut self, row: Register) {
+        self.record(|s| row.write_to(&mut s.processor));
+    }
+
+    fn instruction_row(&mut self, row: InstructionMatrixRow) {
+        self.record(|s| s.instruction.push(row));
+    }
+
+    fn memory_row(&mut self, row: MemoryMatrixRow) {
+        self.record(|s| s.memory.push(row));
+    }
+
+    fn input_row(&mut self, row: IOMatrixRow) {
+        self.record(|s| row.write_to(&mut s.input));
+    }
+
+    fn output_row(&mut self, row: IOMatrixRow) {
+        self.record(|s| row.write_to(&mut s.output));
+    }
+
+    fn finish(&mut self) {
+        self.record(|s| {
+            s.processor.flush()?;
+            s.input.flush()?;
+            s.output.flush()?;
+            s.instruction.finish()?;
+            s.memory.finish()
+        });
+    }
+}
+
+/// Loads a trace written by [`FileTraceSink`] back into a `Matrix`. Only
+/// meant for traces that fit in memory.
+pub fn load_matrix(
+    dir: impl AsRef<Path>,
+    program: Vec<InstructionMatrixRow>,
+) -> io::Result<Matrix> {
+    let dir = dir.as_ref();
+    Ok(Matrix {
+        processor_matrix: TableReader::open(dir.join(PROCESSOR_FILE))?
+            .collect::<io::Result<_>>()?,
+        instruction_matrix: TableReader::open(dir.join(INSTRUCTION_FILE))?
+            .collect::<io::Result<_>>()?,
+        memory_matrix: TableReader::open(dir.join(MEMORY_FILE))?.collect::<io::Result<_>>()?,
+        input_matrix: TableReader::open(dir.join(INPUT_FILE))?.collect::<io::Result<_>>()?,
+        output_matrix: TableReader::open(dir.join(OUTPUT_FILE))?.collect::<io::Result<_>>()?,
+        program,
+    })
+}
@@ -0,0 +1,175 @@
+//! Hooks into the execution of an [`Interpreter`](crate::interpreter::Interpreter).
+//! The interpreter builds its `Matrix` through the [`Recorder`] tracer, other
+//! tools observe a run the same way (`Interpreter::run_traced`), and tuples
+//! combine tracers.
+use crate::code::Instruction;
+use crate::interpreter::{program_rows, FieldExt, Register};
+use crate::matrix::{IOMatrixRow, InstructionMatrixRow, MemoryMatrixRow};
+use crate::sink::TraceSink;
+use alloc::collections::BTreeMap;
+use core::fmt;
+use halo2_proofs::halo2curves::bn256::Fr;
+
+/// Callbacks of a run, all doing nothing by default.
+pub trait Tracer {
+    /// Once, before the first instruction.
+    fn on_start(&mut self, _: &[Instruction]) {}
+    /// Before every instruction, with the registers it executes on.
+    fn on_step(&mut self, _: &Register) {}
+    /// `+`, `-` or `,` stored `new` in cell `mp`, which held `old`.
+    fn on_memory_write(&mut self, _mp: usize, _old: Fr, _new: Fr) {}
+    /// `,` read a value, given as its input table row.
+    fn on_input(&mut self, _: &IOMatrixRow) {}
+    /// `.` wrote a value, given as its output table row.
+    fn on_output(&mut self, _: &IOMatrixRow) {}
+    /// Once the program halted, with the final registers.
+    fn on_halt(&mut self, _: &Register) {}
+}
+
+impl Tracer for () {}
+
+impl<T: Tracer + ?Sized> Tracer for &mut T {
+    fn on_start(&mut self, code: &[Instruction]) {
+        (**self).on_start(code)
+    }
+    fn on_step(&mut self, register: &Register) {
+        (**self).on_step(register)
+    }
+    fn on_memory_write(&mut self, mp: usize, old: Fr, new: Fr) {
+        (**self).on_memory_write(mp, old, new)
+    }
+    fn on_input(&mut self, row: &IOMatrixRow) {
+        (**self).on_input(row)
+    }
+    fn on_output(&mut self, row: &IOMatrixRow) {
+        (**self).on_output(row)
+    }
+    fn on_halt(&mut self, register: &Register) {
+        (**self).on_halt(register)
+    }
+}
+
+impl<A: Tracer, B: Tracer> Tracer for (A, B) {
+    fn on_start(&mut self, code: &[Instruction]) {
+        self.0.on_start(code);
+        self.1.on_start(code);
+    }
+    fn on_step(&mut self, register: &Register) {
+        self.0.on_step(register);
+        self.1.on_step(register);
+    }
+    fn on_memory_write(&mut self, mp: usize, old: Fr, new: Fr) {
+        self.0.on_memory_write(mp, old, new);
+        self.1.on_memory_write(mp, old, new);
+    }
+    fn on_input(&mut self, row: &IOMatrixRow) {
+        self.0.on_input(row);
+        self.1.on_input(row);
+    }
+    fn on_output(&mut self, row: &IOMatrixRow) {
+        self.0.on_output(row);
+        self.1.on_output(row);
+    }
+    fn on_halt(&mut self, register: &Register) {
+        self.0.on_halt(register);
+        self.1.on_halt(register);
+    }
+}
+
+/// Records the execution trace into a [`TraceSink`], finishing it on halt.
+pub struct Recorder<'a, S>(pub &'a mut S);
+
+impl<S: TraceSink> Tracer for Recorder<'_, S> {
+    fn on_start(&mut self, code: &[Instruction]) {
+        for row in program_rows(code) {
+            self.0.instruction_row(row);
+        }
+    }
+
+    fn on_step(&mut self, register: &Register) {
+        self.0.processor_row(register.clone());
+        self.0.instruction_row(InstructionMatrixRow::from(register));
+        self.0.memory_row(MemoryMatrixRow::from(register));
+    }
+
+    fn on_input(&mut self, row: &IOMatrixRow) {
+        self.0.input_row(row.clone());
+    }
+
+    fn on_output(&mut self, row: &IOMatrixRow) {
+        self.0.output_row(row.clone());
+    }
+
+    fn on_halt(&mut self, register: &Register) {
+        self.0.processor_row(register.clone());
+        self.0.memory_row(MemoryMatrixRow::from(register));
+        self.0.instruction_row(InstructionMatrixRow::from(register));
+        self.0.finish();
+    }
+}
+
+/// Counts the executed instructions by opcode.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstructionCounter {
+    pub counts: BTreeMap<u8, u64>,
+}
+
+impl InstructionCounter {
+    pub fn count(&self, opcode: u8) -> u64 {
+        self.counts.get(&opcode).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+}
+
+impl Tracer for InstructionCounter {
+    fn on_step(&mut self, register: &Register) {
+        let opcode = register.current_instruction.get_lower_128() as u8;
+        *self.counts.entry(opcode).or_default() += 1;
+    }
+}
+
+/// Writes one line per event, e.g. into a `String`. Write errors are ignored.
+pub struct Logger<W>(pub W);
+
+impl<W: fmt::Write> Tracer for Logger<W> {
+    fn on_start(&mut self, code: &[Instruction]) {
+        let _ = writeln!(self.0, "start: {} instructions", code.len());
+    }
+
+    fn on_step(&mut self, r: &Register) {
+        let _ = writeln!(
+            self.0,
+            "clk={} ip={} ci='{}' mp={} mv={}",
+            r.cycle.get_lower_128(),
+            r.ip(),
+            r.current_instruction.get_lower_128() as u8 as char,
+            r.mp(),
+            r.memory_value.get_lower_128()
+        );
+    }
+
+    fn on_memory_write(&mut self, mp: usize, old: Fr, new: Fr) {
+        let _ = writeln!(
+            self.0,
+            "  cell {}: {} -> {}",
+            mp,
+            old.get_lower_128(),
+            new.get_lower_128()
+        );
+    }
+
+    fn on_input(&mut self, row: &IOMatrixRow) {
+        let _ = writeln!(self.0, "  input {}", row.value.get_lower_128());
+    }
+
+    fn on_output(&mut self, row: &IOMatrixRow) {
+        let _ = writeln!(self.0, "  output {}", row.value.get_lower_128());
+    }
+
+    fn on_halt(&mut self, r: &Register) {
+        let _ = writeln!(self.0, "halt: clk={}", r.cycle.get_lower_128());
+    }
+}
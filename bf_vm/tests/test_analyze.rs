@@ -0,0 +1,84 @@
+use bf_vm::analyze::{analyze, line_col, Diagnostic, Lint, Severity};
+use bf_vm::code::{self, CompileError};
+
+fn lints(source: &[u8]) -> Vec<(Severity, Lint, std::ops::Range<usize>)> {
+    analyze(source, 8)
+        .diagnostics
+        .into_iter()
+        .map(
+            |Diagnostic {
+                 severity,
+                 lint,
+                 span,
+             }| (severity, lint, span),
+        )
+        .collect()
+}
+
+#[test]
+fn test_analyze_brackets() {
+    assert_eq!(
+        code::unmatched_brackets(b"][[]"),
+        vec![
+            CompileError::UnmatchedClose { pos: 0 },
+            CompileError::UnmatchedOpen { pos: 1 }
+        ]
+    );
+    let analysis = analyze(b"+]\n[[-]", 8);
+    assert!(analysis.has_errors());
+    assert_eq!(analysis.tape_bound, None);
+    let spans = analysis
+        .diagnostics
+        .iter()
+        .map(|d| d.span.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(spans, vec![1..2, 3..4]);
+    assert_eq!(
+        analysis.diagnostics[1].to_string(),
+        "error: unmatched '[' at offset 3"
+    );
+    assert_eq!(line_col(b"+]\n[[-]", 3), (2, 1));
+}
+
+#[test]
+fn test_analyze_loops() {
+    use Severity::*;
+    // comment loop at the start, and a loop right after another one
+    assert_eq!(
+        lints(b"[comment.]+[-][+]"),
+        vec![
+            (Warning, Lint::DeadLoop, 0..10),
+            (Warning, Lint::DeadLoop, 14..17)
+        ]
+    );
+    assert_eq!(lints(b"+[]"), vec![(Error, Lint::InfiniteLoop, 1..3)]);
+    assert_eq!(lints(b",[]"), vec![(Warning, Lint::InfiniteLoop, 1..3)]);
+    // `[]` inside a loop that runs
+    assert_eq!(lints(b"+[[]-]"), vec![(Error, Lint::InfiniteLoop, 2..4)]);
+    assert!(lints(include_bytes!("../../res/neptune_tutorial.bf")).is_empty());
+    assert!(lints(include_bytes!("../../res/hello_world.bf")).is_empty());
+}
+
+#[test]
+fn test_analyze_tape() {
+    use Severity::*;
+    assert_eq!(lints(b">+<<"), vec![(Error, Lint::TapeUnderflow, 3..4)]);
+    // only when a loop is entered
+    assert_eq!(lints(b",[<]"), vec![(Warning, Lint::TapeUnderflow, 2..3)]);
+
+    assert_eq!(analyze(b">>+[>+<-]<", 8).tape_bound, Some(4));
+    assert_eq!(analyze(b"+[>+]", 8).tape_bound, None);
+    assert_eq!(
+        analyze(include_bytes!("../../res/hello_world.bf"), 8).tape_bound,
+        Some(5)
+    );
+}
+
+#[test]
+fn test_analyze_bits() {
+    // 64-bit cells: `-` on 0 wraps to the largest value, `+` back to 0
+    let analysis = analyze(b"-+[]", 64);
+    assert_eq!(analysis.diagnostics.len(), 1);
+    assert_eq!(analysis.diagnostics[0].lint, Lint::DeadLoop);
+    assert_eq!(analyze(b"-[]", 64).diagnostics[0].lint, Lint::InfiniteLoop);
+}
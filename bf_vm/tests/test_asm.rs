@@ -0,0 +1,117 @@
+use bf_vm::asm::{assemble, AsmErrorKind, Location};
+use bf_vm::code;
+use bf_vm::interpreter::Interpreter;
+
+fn lower(source: &str) -> String {
+    let assembly = assemble("main", source.as_bytes(), |_| None).unwrap();
+    String::from_utf8(assembly.bf).unwrap()
+}
+
+fn error(source: &str) -> (AsmErrorKind, usize) {
+    let e = assemble("main", source.as_bytes(), |_| None).unwrap_err();
+    (e.kind, e.offset)
+}
+
+#[test]
+fn test_asm_lowering() {
+    assert_eq!(lower("+*3 >*2 -"), "+++>>-");
+    assert_eq!(lower("+ # comment, not code.\n."), "+.");
+    assert_eq!(
+        lower("cell x 0 cell y 3 @y + @x [- @y + @x] @y"),
+        ">>>+<<<[->>>+<<<]>>>"
+    );
+    assert_eq!(
+        lower("def inc2 { +*2 } def inc4 { inc2 inc2 } inc4 ."),
+        "++++."
+    );
+    // braces in comments do not end a macro
+    assert_eq!(lower("def f { + # ends with }\n + } f ."), "++.");
+
+    // prints 'A' once assembled and compiled
+    let assembly = assemble("main", b"cell a 1 @a +*65 .", |_| None).unwrap();
+    let (program, _) = assembly.compile().unwrap();
+    let result = Interpreter::builder(program).run();
+    assert_eq!(result.output, b"A");
+}
+
+#[test]
+fn test_asm_include() {
+    let lib = b"def nl { +*10 . [-] }";
+    let resolve = |path: &str| (path == "lib.bfa").then(|| lib.to_vec());
+    let assembly = assemble(
+        "main",
+        b"include \"lib.bfa\" include \"lib.bfa\" nl",
+        resolve,
+    )
+    .unwrap();
+    assert_eq!(assembly.files, vec!["main", "lib.bfa"]);
+    assert_eq!(assembly.bf, b"++++++++++.[-]");
+
+    // the source map goes through the assembler and the compiler
+    let (program, map) = assembly.compile().unwrap();
+    assert_eq!(program.len(), map.len());
+    let at = |offset| Location { file: 1, offset };
+    assert_eq!((map[0], map[10], map[13]), (at(9), at(14), at(18)));
+    assert_eq!(code::compile(assembly.bf.clone()), program);
+}
+
+#[test]
+fn test_asm_include_nested() {
+    // lib/a.bfa includes b.bfa next to it, not next to main
+    let resolve = |path: &str| match path {
+        "src/lib/a.bfa" => Some(b"include \"b.bfa\" def a { b b }".to_vec()),
+        "src/lib/b.bfa" => Some(b"def b { + }".to_vec()),
+        _ => None,
+    };
+    let assembly = assemble("src/main", b"include \"lib/a.bfa\" a .", resolve).unwrap();
+    assert_eq!(
+        assembly.files,
+        vec!["src/main", "src/lib/a.bfa", "src/lib/b.bfa"]
+    );
+    assert_eq!(assembly.bf, b"++.");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_asm_include_file() {
+    let dir = std::env::temp_dir().join(format!("bfasm-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("main.bfa"), "include \"lib/a.bfa\" a .").unwrap();
+    std::fs::write(dir.join("lib/a.bfa"), "include \"b.bfa\" def a { b b }").unwrap();
+    std::fs::write(dir.join("lib/b.bfa"), "def b { + }").unwrap();
+    let assembly = bf_vm::asm::assemble_file(&dir.join("main.bfa"));
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(assembly.unwrap().bf, b"++.");
+}
+
+#[test]
+fn test_asm_errors() {
+    assert_eq!(error("+ foo"), (AsmErrorKind::UnknownName("foo".into()), 2));
+    assert_eq!(error("+ a"), (AsmErrorKind::UnknownName("a".into()), 2));
+    assert_eq!(error("+ !"), (AsmErrorKind::UnexpectedChar('!'), 2));
+    assert_eq!(
+        error("def f { f } f"),
+        (AsmErrorKind::RecursiveMacro("f".into()), 8)
+    );
+    assert_eq!(
+        error("cell x 0 cell x 1"),
+        (AsmErrorKind::DuplicateName("x".into()), 9)
+    );
+    assert_eq!(
+        error("cell x 0 [>] @x"),
+        (AsmErrorKind::UnknownPointer("x".into()), 13)
+    );
+    assert_eq!(
+        error("cell x 2 + [@x >]"),
+        (AsmErrorKind::UnbalancedLoop("x".into()), 12)
+    );
+    assert_eq!(error("+[ [-]"), (AsmErrorKind::UnmatchedOpen, 1));
+    assert_eq!(error("-]"), (AsmErrorKind::UnmatchedClose, 1));
+    assert_eq!(
+        error("include \"nowhere\""),
+        (AsmErrorKind::IncludeNotFound("nowhere".into()), 0)
+    );
+    let e = assemble("main", b"include \"main\"", |_| None).unwrap_err();
+    assert_eq!(e.kind, AsmErrorKind::RecursiveInclude("main".into()));
+    assert_eq!(e.to_string(), "main:0: main includes itself");
+}
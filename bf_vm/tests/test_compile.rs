@@ -1,15 +1,73 @@
-use bf_vm::code;
+use bf_vm::code::{self, CompileError, Instruction};
+use bf_vm::interpreter::Interpreter;
+use bf_vm::native::NativeInterpreter;
 use halo2_proofs::halo2curves::bn256::Fr;
 
 #[test]
 fn test_compile_neptune() {
     let output = code::compile("++>,<[>+.<-]".as_bytes().to_vec());
-    let expect: Vec<Fr> = vec![
-        '+' as u64, '+' as u64, '>' as u64, ',' as u64, '<' as u64, '[' as u64, 14, '>' as u64,
-        '+' as u64, '.' as u64, '<' as u64, '-' as u64, ']' as u64, 7,
-    ]
-    .iter()
-    .map(|&x| Fr::from(x))
-    .collect();
+    let expect = vec![
+        Instruction::new(b'+', 0),
+        Instruction::new(b'+', 0),
+        Instruction::new(b'>', 0),
+        Instruction::new(b',', 0),
+        Instruction::new(b'<', 0),
+        Instruction::new(b'[', 12),
+        Instruction::new(b'>', 0),
+        Instruction::new(b'+', 0),
+        Instruction::new(b'.', 0),
+        Instruction::new(b'<', 0),
+        Instruction::new(b'-', 0),
+        Instruction::new(b']', 6),
+    ];
     assert_eq!(output, expect);
 }
+
+#[test]
+fn test_compile_operand_values() {
+    // jump targets that equal the '[' and ']' opcodes are plain operands
+    let source = format!("{}[-].", "+".repeat(90));
+    let program = code::compile(source.into_bytes());
+    assert_eq!(program[90], Instruction::new(b'[', 93));
+    assert_eq!(program[92], Instruction::new(b']', 91));
+
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.run();
+    assert_eq!(vm.matrix.output_matrix.len(), 1);
+    assert_eq!(vm.matrix.output_matrix[0].value, Fr::zero());
+}
+
+#[test]
+fn test_compile_errors() {
+    assert_eq!(
+        code::try_compile(b"+[>[-]".to_vec()),
+        Err(CompileError::UnmatchedOpen { pos: 1 })
+    );
+    assert_eq!(
+        code::try_compile(b"+ ]".to_vec()),
+        Err(CompileError::UnmatchedClose { pos: 2 })
+    );
+}
+
+#[test]
+fn test_large_program() {
+    // 20000 cleared cells, 20000 increments and a `.`: 100001 instructions,
+    // the jump targets of the last loops need more than 16 bits
+    let mut source = "+[-]".repeat(20000);
+    source.push_str(&"+".repeat(20000));
+    source.push('.');
+    let program = code::try_compile(source.into_bytes()).unwrap();
+    assert_eq!(program.len(), 100_001);
+    assert_eq!(program[79_997], Instruction::new(b'[', 80_000));
+    assert_eq!(program[79_999], Instruction::new(b']', 79_998));
+
+    let mut native = NativeInterpreter::<u8>::new(program.clone());
+    assert_eq!(native.run(), vec![(20000 % 256) as u8]);
+
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.run();
+    assert_eq!(vm.matrix.output_matrix[0].value, Fr::from(20000 % 256));
+    assert_eq!(vm.matrix.processor_matrix.len() as u64, native.cycles + 1);
+}
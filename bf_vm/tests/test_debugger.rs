@@ -0,0 +1,89 @@
+use bf_vm::code;
+use bf_vm::debugger::{Breakpoint, Debugger, StopReason};
+use bf_vm::interpreter::{Arithmetic, Interpreter, Trap};
+use halo2_proofs::halo2curves::bn256::Fr;
+
+fn neptune_vm() -> Interpreter {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm
+}
+
+#[test]
+fn test_step_matches_run() {
+    let mut vm = neptune_vm();
+    vm.run();
+
+    let mut dbg = Debugger::new(neptune_vm());
+    while dbg.step() != StopReason::Halted {}
+    assert_eq!(
+        dbg.vm.matrix.processor_matrix.len(),
+        vm.matrix.processor_matrix.len()
+    );
+    assert_eq!(
+        dbg.vm.matrix.memory_matrix.len(),
+        vm.matrix.memory_matrix.len()
+    );
+    assert_eq!(
+        dbg.vm.matrix.instruction_matrix.len(),
+        vm.matrix.instruction_matrix.len()
+    );
+    assert_eq!(dbg.step(), StopReason::Halted);
+}
+
+#[test]
+fn test_breakpoints_and_watchpoints() {
+    let source = include_bytes!("../../res/neptune_tutorial.bf").to_vec();
+    let (_, source_map) = code::compile_with_source_map(source.clone());
+    let mut dbg = Debugger::new(neptune_vm()).with_source_map(source_map);
+
+    // the `.` inside the loop is hit once per iteration
+    let putchar = source.iter().position(|&c| c == b'.').unwrap();
+    dbg.add_breakpoint(Breakpoint::Source(putchar));
+    assert_eq!(
+        dbg.cont(),
+        StopReason::Breakpoint(Breakpoint::Source(putchar))
+    );
+    assert_eq!(dbg.register().ip(), 8);
+    assert_eq!(dbg.register().memory_value, Fr::from('b' as u64));
+    assert_eq!(
+        dbg.cont(),
+        StopReason::Breakpoint(Breakpoint::Source(putchar))
+    );
+    assert_eq!(dbg.register().memory_value, Fr::from('c' as u64));
+    dbg.remove_breakpoint(Breakpoint::Source(putchar));
+
+    dbg.watch(0);
+    assert_eq!(
+        dbg.cont(),
+        StopReason::Watchpoint {
+            cell: 0,
+            old: Fr::from(1),
+            new: Fr::from(0)
+        }
+    );
+    assert_eq!(dbg.cont(), StopReason::Halted);
+    assert_eq!(dbg.tape()[0], Fr::from(0));
+}
+
+#[test]
+fn test_trap() {
+    let mut vm = Interpreter::new();
+    vm.set_arithmetic(Arithmetic::Strict);
+    vm.set_code(code::compile(b"+--".to_vec()));
+    let mut dbg = Debugger::new(vm);
+    assert_eq!(
+        dbg.cont(),
+        StopReason::Trap(Trap::Underflow {
+            cycle: 2,
+            ip: 2,
+            mp: 0
+        })
+    );
+    // the trapping instruction was not executed
+    assert_eq!(dbg.register().ip(), 2);
+    assert_eq!(dbg.vm.matrix.processor_matrix.len(), 2);
+    assert!(matches!(dbg.step(), StopReason::Trap(_)));
+}
@@ -0,0 +1,62 @@
+use bf_vm::code;
+use bf_vm::dump::{self, Format, Table};
+use bf_vm::interpreter::Interpreter;
+use bf_vm::matrix::Matrix;
+use halo2_proofs::halo2curves::bn256::Fr;
+
+fn run(input: &str) -> Matrix {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen(input));
+    vm.run();
+    vm.matrix
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(dump::value(&Fr::from(42)), "42");
+    assert_eq!(dump::value(&-Fr::from(3)), "-3");
+    assert_eq!(dump::opcode(&Fr::from('[' as u64)), "[");
+    assert_eq!(dump::opcode(&Fr::from(14)), "14");
+
+    let matrix = run("a");
+    let csv = dump::render_table(&matrix, Table::Output, Format::Csv);
+    assert_eq!(csv, "clk,value,diff\n8,98,0\n14,99,5\n");
+    let pretty = dump::render_table(&matrix, Table::Processor, Format::Pretty);
+    let mut lines = pretty.lines();
+    let header: Vec<&str> = lines.next().unwrap().split_whitespace().collect();
+    assert_eq!(
+        header,
+        ["clk", "ip", "ci", "ni", "arg", "mp", "mv", "mv_inv", "eof"]
+    );
+    let row: Vec<&str> = lines.next().unwrap().split_whitespace().collect();
+    assert_eq!(row, ["0", "0", "+", "+", "0", "0", "0", "0", "0"]);
+}
+
+#[test]
+fn test_diff() {
+    assert_eq!(dump::diff(&run("a"), &run("a")), None);
+
+    let d = dump::diff(&run("a"), &run("b")).unwrap();
+    assert_eq!(d.table, Table::Processor);
+    assert_eq!(d.row, 4);
+    let report = dump::render_diff(&run("a"), &run("b"), 1);
+    assert!(report.starts_with("first divergence in processor table at row 4\n"));
+    assert!(report.lines().any(|l| l.trim_start().starts_with(">>")));
+}
+
+#[test]
+fn test_diff_eof() {
+    // a trace that differs only in its eof flag
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b",[.,]".to_vec()));
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    let mut forged = vm.matrix.clone();
+    forged.processor_matrix[1].eof = Fr::from(1);
+
+    let d = dump::diff(&vm.matrix, &forged).unwrap();
+    assert_eq!(d.table, Table::Processor);
+    assert_eq!(d.row, 1);
+}
@@ -0,0 +1,92 @@
+use bf_vm::code;
+use bf_vm::interpreter::{Interpreter, Trap};
+use bf_vm::io::{ByteSink, IoError};
+use halo2_proofs::halo2curves::bn256::Fr;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const CAT: &[u8] = b",[.,]";
+
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl ByteSink for Shared {
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError> {
+        self.0.borrow_mut().push(byte);
+        Ok(())
+    }
+}
+
+struct Broken;
+
+impl ByteSink for Broken {
+    fn write_byte(&mut self, _: u8) -> Result<(), IoError> {
+        Err(IoError)
+    }
+}
+
+#[test]
+fn test_io_reader_matches_input() {
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(CAT.to_vec()));
+    vm.set_input(code::easygen("hello"));
+    vm.run();
+
+    let mut streamed = Interpreter::new();
+    streamed.set_code(code::compile(CAT.to_vec()));
+    streamed.set_reader(b"hello".to_vec().into_iter());
+    streamed.run();
+    assert_eq!(streamed.matrix, vm.matrix);
+    assert_eq!(streamed.output, b"hello");
+    assert_eq!(vm.output, b"hello");
+}
+
+#[test]
+fn test_io_writer() {
+    let out = Shared::default();
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(
+        include_bytes!("../../res/hello_world.bf").to_vec(),
+    ));
+    vm.set_writer(out.clone());
+    vm.run();
+    assert_eq!(*out.0.borrow(), b"Hello World!\n");
+    assert!(vm.output.is_empty());
+
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b"+>.".to_vec()));
+    vm.set_writer(Broken);
+    assert_eq!(vm.try_run(), Err(Trap::Io { cycle: 2, ip: 2 }));
+}
+
+#[test]
+fn test_io_eof_flag() {
+    // the flag is raised by the `,` that finds no input, not when the last
+    // byte is read, so the next byte of a stream is never waited for early
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b",>,>,".to_vec()));
+    vm.set_reader(b"a".to_vec().into_iter());
+    vm.run();
+    let eof = vm
+        .matrix
+        .processor_matrix
+        .iter()
+        .map(|r| r.eof)
+        .collect::<Vec<_>>();
+    let (zero, one) = (Fr::zero(), Fr::one());
+    assert_eq!(eof, vec![zero, zero, one, one, one, one]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_io_std() {
+    use bf_vm::io::{Reader, Writer};
+
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(CAT.to_vec()));
+    vm.set_reader(Reader(std::io::Cursor::new(b"cat".to_vec())));
+    vm.set_writer(Writer(std::io::sink()));
+    vm.run();
+    assert_eq!(vm.matrix.output_matrix.len(), 3);
+    assert!(vm.output.is_empty());
+}
@@ -0,0 +1,88 @@
+use bf_vm::code;
+use bf_vm::interpreter::{Arithmetic, EofPolicy, Interpreter};
+use bf_vm::native::NativeInterpreter;
+
+fn assert_same_trace(source: &[u8], input: &str) {
+    assert_same_trace_with_eof(source, input, EofPolicy::default());
+}
+
+fn assert_same_trace_with_eof(source: &[u8], input: &str, eof: EofPolicy) -> Vec<u8> {
+    let mut vm = Interpreter::new();
+    vm.set_eof(eof);
+    vm.set_code(code::compile(source.to_vec()));
+    vm.set_input(code::easygen(input));
+    vm.run();
+
+    let mut native = NativeInterpreter::<u8>::new(code::compile(source.to_vec()));
+    native.set_eof(eof);
+    native.set_input(input.as_bytes());
+    let (output, matrix) = native.trace();
+    assert_eq!(matrix, vm.matrix);
+    assert_eq!(native.run(), output);
+    assert_eq!(native.cycles + 1, vm.matrix.processor_matrix.len() as u64);
+    assert_eq!(vm.output, output);
+    output
+}
+
+#[test]
+fn test_native_trace_matches_interpreter() {
+    assert_same_trace(include_bytes!("../../res/hello_world.bf"), "");
+    assert_same_trace(include_bytes!("../../res/neptune_tutorial.bf"), "a");
+}
+
+#[test]
+fn test_native_run() {
+    let mut vm = NativeInterpreter::<u8>::new(code::compile(
+        include_bytes!("../../res/hello_world.bf").to_vec(),
+    ));
+    assert_eq!(vm.run(), b"Hello World!\n");
+
+    let mut vm = NativeInterpreter::<u16>::new(code::compile(b"-.".to_vec()));
+    assert_eq!(vm.run(), vec![u16::MAX]);
+}
+
+#[test]
+fn test_native_eof() {
+    // cat, in the idiom of each EOF convention
+    let cat = [
+        (EofPolicy::Zero, &b",[.,]"[..]),
+        (EofPolicy::Max, &b",+[-.,+]"[..]),
+        (EofPolicy::Unchanged, &b",[.[-],]"[..]),
+    ];
+    for (eof, source) in cat {
+        assert_eq!(assert_same_trace_with_eof(source, "cat", eof), b"cat");
+        assert_eq!(assert_same_trace_with_eof(source, "", eof), b"");
+    }
+    assert_eq!(
+        assert_same_trace_with_eof(b"+,.,.", "", EofPolicy::Max),
+        vec![255, 255]
+    );
+    assert_eq!(
+        assert_same_trace_with_eof(b"+,.", "", EofPolicy::Unchanged),
+        vec![1]
+    );
+}
+
+#[test]
+#[should_panic(expected = "'+' overflows cell 0 at ip 255 (cycle 255)")]
+fn test_native_strict_overflow() {
+    let mut native = NativeInterpreter::<u8>::new(code::compile(vec![b'+'; 256]));
+    native.set_arithmetic(Arithmetic::Strict);
+    native.run();
+}
+
+#[test]
+fn test_native_initial_tape() {
+    let source = b">[-<+>]<.";
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(source.to_vec()));
+    vm.set_tape(code::easygen("\x03\x04"));
+    vm.run();
+
+    let mut native = NativeInterpreter::<u8>::new(code::compile(source.to_vec()));
+    native.set_tape(&[3, 4]);
+    let (output, matrix) = native.trace();
+    assert_eq!(matrix, vm.matrix);
+    assert_eq!(output, vec![7]);
+    assert_eq!(native.memory, vec![7, 0]);
+}
@@ -0,0 +1,36 @@
+use bf_vm::code;
+use bf_vm::interpreter::Interpreter;
+use bf_vm::profile::Profiler;
+
+#[test]
+fn test_profile_loops() {
+    let source = b"++[>+++[>+<-]<-]";
+    let (program, source_map) = code::compile_with_source_map(source.to_vec());
+    let mut profiler = Profiler::new().with_source_map(source_map);
+    let result = Interpreter::builder(program).run_with(&mut profiler);
+    assert_eq!(profiler.cycles(), result.cycles);
+    assert_eq!(profiler.k(), 9);
+
+    let loops = profiler.loops();
+    assert_eq!(loops.len(), 2);
+    let (outer, inner) = (&loops[0], &loops[1]);
+    assert_eq!((outer.start, outer.end, outer.depth), (2, 15, 0));
+    assert_eq!((inner.start, inner.end, inner.depth), (7, 12, 1));
+    assert_eq!((outer.entries, outer.iterations), (1, 2));
+    assert_eq!((inner.entries, inner.iterations), (2, 6));
+    assert_eq!(outer.cycles, result.cycles - 2);
+    assert_eq!(outer.self_cycles, outer.cycles - inner.cycles);
+    assert_eq!(inner.self_cycles, inner.cycles);
+    assert_eq!(inner.cycles, 2 + 6 * 5);
+
+    assert_eq!(
+        profiler.folded(),
+        format!(
+            "program 2\nprogram;loop@2 {}\nprogram;loop@2;loop@7 {}\n",
+            outer.self_cycles, inner.cycles
+        )
+    );
+    let report = profiler.report(1);
+    assert!(report.starts_with(&format!("{} cycles over 16 instructions", result.cycles)));
+    assert_eq!(report.lines().count(), 7);
+}
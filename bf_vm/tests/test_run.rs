@@ -1,5 +1,6 @@
 use bf_vm::code;
-use bf_vm::interpreter::Interpreter;
+use bf_vm::interpreter::{Arithmetic, HaltReason, Interpreter, Trap};
+use bf_vm::native::NativeInterpreter;
 use halo2_proofs::halo2curves::bn256::Fr;
 use halo2curves::ff::PrimeField;
 
@@ -49,7 +50,7 @@ fn test_run_neptune() {
     vm.run();
     assert_eq!(vm.matrix.processor_matrix.len(), 19);
     assert_eq!(vm.matrix.memory_matrix.len(), 19);
-    assert_eq!(vm.matrix.instruction_matrix.len(), 33);
+    assert_eq!(vm.matrix.instruction_matrix.len(), 31);
     assert_eq!(
         vm.matrix
             .input_matrix
@@ -73,24 +74,146 @@ fn test_run_neptune() {
             p.instruction_pointer.get_lower_128() as u8,
             p.current_instruction.get_lower_128() as u8,
             p.next_instruction.get_lower_128() as u8,
+            p.operand.get_lower_128() as u8,
         ]);
     }
 
     let expect = vec![
-        [0, 43, 43],
-        [1, 43, 62],
-        [2, 62, 44],
-        [3, 44, 60],
-        [4, 60, 91],
-        [5, 91, 14],
-        [14, 0, 0],
-        [7, 62, 43],
-        [8, 43, 46],
-        [9, 46, 60],
-        [10, 60, 45],
-        [11, 45, 93],
-        [12, 93, 7],
-        [7, 62, 43],
+        [0, 43, 43, 0],
+        [1, 43, 62, 0],
+        [2, 62, 44, 0],
+        [3, 44, 60, 0],
+        [4, 60, 91, 0],
+        [5, 91, 62, 12],
+        [6, 62, 43, 0],
+        [7, 43, 46, 0],
+        [8, 46, 60, 0],
+        [9, 60, 45, 0],
+        [10, 45, 93, 0],
+        [11, 93, 0, 6],
     ];
     assert_eq!(program, expect);
 }
+
+#[test]
+fn test_run_strict_arithmetic() {
+    let run = |source: &[u8], bits: u64, arithmetic: Arithmetic| {
+        let mut vm = Interpreter::new();
+        vm.set_bits(bits);
+        vm.set_arithmetic(arithmetic);
+        vm.set_code(code::compile(source.to_vec()));
+        vm.try_run().map(|_| vm.matrix)
+    };
+
+    // wrapping is the default and never traps
+    assert!(run(b">-.", 8, Arithmetic::Wrapping).is_ok());
+    assert_eq!(
+        run(b">-.", 8, Arithmetic::Strict).unwrap_err(),
+        Trap::Underflow {
+            cycle: 1,
+            ip: 1,
+            mp: 1
+        }
+    );
+    assert_eq!(
+        run(&[b'+'; 16], 4, Arithmetic::Strict).unwrap_err(),
+        Trap::Overflow {
+            cycle: 15,
+            ip: 15,
+            mp: 0
+        }
+    );
+
+    // the full range is still reachable
+    let matrix = run(b"+++++++++++++++.[-].", 4, Arithmetic::Strict).unwrap();
+    assert_eq!(
+        matrix
+            .output_matrix
+            .iter()
+            .map(|v| v.value)
+            .collect::<Vec<_>>(),
+        vec![Fr::from(15), Fr::from(0)]
+    );
+}
+
+#[test]
+fn test_run_initial_tape() {
+    // adds cell 1 into cell 0
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b">[-<+>]<.".to_vec()));
+    vm.set_tape(code::easygen("\x03\x04\x09"));
+    vm.run();
+    assert_eq!(vm.matrix.output_matrix[0].value, Fr::from(7));
+    // cell 2 is never touched, so it is not part of the trace
+    assert_eq!(vm.matrix.initial_tape(), code::easygen("\x03\x04"));
+    assert_eq!(vm.matrix.final_tape(), code::easygen("\x07\x00"));
+    assert_eq!(vm.memory, code::easygen("\x07\x00\x09"));
+}
+
+#[test]
+fn test_run_builder() {
+    let source = include_bytes!("../../res/neptune_tutorial.bf");
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(source.to_vec()));
+    vm.set_input(code::easygen("a"));
+    vm.run();
+
+    let builder = Interpreter::builder(code::compile(source.to_vec())).input(code::easygen("a"));
+    let result = builder.run();
+    assert_eq!(result.halted_reason, HaltReason::Halted);
+    assert_eq!(result.trace, vm.matrix);
+    assert_eq!(result.output, vm.output);
+    assert_eq!(result.cycles + 1, vm.matrix.processor_matrix.len() as u64);
+    assert_eq!(
+        result.max_mp as u128,
+        vm.matrix
+            .memory_matrix
+            .last()
+            .unwrap()
+            .memory_pointer
+            .get_lower_128()
+    );
+    // runs do not share state
+    assert_eq!(builder.run(), result);
+}
+
+#[test]
+fn test_run_builder_limits() {
+    let builder = Interpreter::builder(code::compile(b"+[>+]".to_vec())).max_cycles(100);
+    let result = builder.run();
+    assert_eq!(result.halted_reason, HaltReason::StepLimit);
+    assert_eq!(result.cycles, 100);
+    assert_eq!(result.trace.processor_matrix.len(), 100);
+
+    let result = Interpreter::builder(code::compile(b"+>-".to_vec()))
+        .arithmetic(Arithmetic::Strict)
+        .run();
+    assert_eq!(
+        result.halted_reason,
+        HaltReason::Trap(Trap::Underflow {
+            cycle: 2,
+            ip: 2,
+            mp: 1
+        })
+    );
+    assert_eq!(result.max_mp, 1);
+}
+
+#[test]
+fn test_run_tape_underflow() {
+    // `<` on cell 0 traps, in either arithmetic
+    let program = code::compile(b">+<<.".to_vec());
+    let trap = Trap::TapeUnderflow { cycle: 3, ip: 3 };
+
+    let mut vm = Interpreter::new();
+    vm.set_code(program.clone());
+    assert_eq!(vm.try_run(), Err(trap));
+
+    let result = Interpreter::builder(program.clone()).run();
+    assert_eq!(result.halted_reason, HaltReason::Trap(trap));
+    assert_eq!(result.trace.processor_matrix.len(), 3);
+
+    let mut native = NativeInterpreter::<u8>::new(program);
+    native.set_arithmetic(Arithmetic::Strict);
+    assert_eq!(native.try_run(), Err(trap));
+}
@@ -0,0 +1,8 @@
+use bf_vm::sizing::k_for;
+
+#[test]
+fn test_k_for() {
+    assert_eq!(k_for(10, 100, 8), 9);
+    assert_eq!(k_for(10, 900, 8), 10);
+    assert_eq!(k_for(10, 100, 16), 17);
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "std")]
+
+use bf_vm::code;
+use bf_vm::interpreter::Interpreter;
+use bf_vm::native::NativeInterpreter;
+use bf_vm::stream::{load_matrix, FileTraceSink};
+
+fn assert_streamed_trace(name: &str, source: &[u8], input: &str) {
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(source.to_vec()));
+    vm.set_input(code::easygen(input));
+    vm.run();
+
+    let dir = std::env::temp_dir().join(format!("bf_vm_stream_{}_{}", name, std::process::id()));
+    // small chunks force several sorted runs to be merged
+    let mut sink = FileTraceSink::create(&dir, 16).unwrap();
+    let mut native = NativeInterpreter::<u8>::new(code::compile(source.to_vec()));
+    native.set_input(input.as_bytes());
+    native.trace_into(&mut sink);
+    sink.result().unwrap();
+
+    let matrix = load_matrix(&dir, vm.matrix.program.clone()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(matrix, vm.matrix);
+}
+
+#[test]
+fn test_file_trace_sink() {
+    assert_streamed_trace("hello", include_bytes!("../../res/hello_world.bf"), "");
+    assert_streamed_trace(
+        "neptune",
+        include_bytes!("../../res/neptune_tutorial.bf"),
+        "a",
+    );
+}
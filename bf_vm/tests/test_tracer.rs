@@ -0,0 +1,77 @@
+use bf_vm::code;
+use bf_vm::interpreter::{program_rows, Interpreter};
+use bf_vm::matrix::Matrix;
+use bf_vm::tracer::{InstructionCounter, Logger, Recorder, Tracer};
+use halo2_proofs::halo2curves::bn256::Fr;
+
+#[derive(Default)]
+struct Writes(Vec<(usize, Fr, Fr)>);
+
+impl Tracer for Writes {
+    fn on_memory_write(&mut self, mp: usize, old: Fr, new: Fr) {
+        self.0.push((mp, old, new));
+    }
+}
+
+#[test]
+fn test_tracer_recorder() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program.clone());
+    vm.set_input(code::easygen("a"));
+    vm.run();
+
+    let mut matrix = Matrix {
+        program: program_rows(&program),
+        ..Matrix::default()
+    };
+    let mut counter = InstructionCounter::default();
+    let mut traced = Interpreter::new();
+    traced.set_code(program.clone());
+    traced.set_input(code::easygen("a"));
+    traced.run_traced(&mut (Recorder(&mut matrix), &mut counter));
+    assert_eq!(matrix, vm.matrix);
+    assert_eq!(counter.total() + 1, matrix.processor_matrix.len() as u64);
+    assert_eq!(counter.count(code::GETCHAR), 1);
+    assert_eq!(
+        counter.count(code::PUTCHAR),
+        matrix.output_matrix.len() as u64
+    );
+
+    // the builder records its trace next to the caller's tracer
+    let mut counter = InstructionCounter::default();
+    let result = Interpreter::builder(program)
+        .input(code::easygen("a"))
+        .run_with(&mut counter);
+    assert_eq!(result.trace, vm.matrix);
+    assert_eq!(counter.total(), result.cycles);
+}
+
+#[test]
+fn test_tracer_events() {
+    let mut writes = Writes::default();
+    let mut log = Logger(String::new());
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b"+>,.".to_vec()));
+    vm.set_input(code::easygen("x"));
+    vm.run_traced(&mut (&mut writes, &mut log));
+
+    let (zero, one, x) = (Fr::zero(), Fr::one(), Fr::from(b'x' as u64));
+    assert_eq!(writes.0, vec![(0, zero, one), (1, zero, x)]);
+    assert_eq!(
+        log.0,
+        "start: 4 instructions
+clk=0 ip=0 ci='+' mp=0 mv=0
+  cell 0: 0 -> 1
+clk=1 ip=1 ci='>' mp=0 mv=1
+clk=2 ip=2 ci=',' mp=1 mv=0
+  input 120
+  cell 1: 0 -> 120
+clk=3 ip=3 ci='.' mp=1 mv=120
+  output 120
+halt: clk=4
+"
+    );
+    // a tracer alone leaves the matrix empty
+    assert!(vm.matrix.processor_matrix.is_empty());
+}
@@ -0,0 +1,62 @@
+//! Proves the same trace with the lookup-based and the BrainSTARK-style I/O
+//! arguments, each with deselector polynomials and with one-hot opcode
+//! selectors, and compares them: `cargo run --release --example compare_modes`.
+use bf_vm::code;
+use bf_vm::interpreter::Interpreter;
+use bf_vm::matrix::Matrix;
+use bf_zk::main_config::VMCircuit;
+use bf_zk::params::{BfCircuitParams, IoMode};
+use bf_zk::prover;
+use bf_zk::report;
+use halo2_proofs::halo2curves::bn256::Fr;
+use std::time::Instant;
+
+const K: u32 = 9;
+
+fn compare(name: &str, matrix: &Matrix, circuit_params: BfCircuitParams) {
+    let params = prover::kzg::setup(K);
+    let circuit = VMCircuit::<Fr>::new(matrix.clone(), circuit_params);
+    println!("{}:", name);
+    for line in report::report(K, &circuit).to_string().lines() {
+        println!("  {}", line);
+    }
+
+    let start = Instant::now();
+    let pk = prover::kzg::keygen(&params, &circuit).unwrap();
+    println!("  keygen:  {:>10?}", start.elapsed());
+
+    let instances = prover::instances(matrix);
+    let start = Instant::now();
+    let proof = prover::kzg::prove(&params, &pk, circuit, &instances).unwrap();
+    println!(
+        "  prove:   {:>10?} ({} bytes)",
+        start.elapsed(),
+        proof.len()
+    );
+
+    let start = Instant::now();
+    prover::kzg::verify(&params, pk.get_vk(), &proof, &instances).unwrap();
+    println!("  verify:  {:>10?}", start.elapsed());
+}
+
+fn main() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+
+    for (name, io, one_hot) in [
+        ("lookup", IoMode::Lookup, false),
+        ("stark", IoMode::Evaluation, false),
+        ("lookup, one-hot", IoMode::Lookup, true),
+        ("stark, one-hot", IoMode::Evaluation, true),
+    ] {
+        let params = BfCircuitParams {
+            io,
+            one_hot,
+            ..Default::default()
+        };
+        compare(name, &vm.matrix, params);
+    }
+}
@@ -0,0 +1,208 @@
+use bf_vm::code;
+use bf_vm::interpreter::{FieldExt, Interpreter};
+use bf_vm::matrix::Matrix;
+use bf_zk::main_config::VMCircuit;
+use bf_zk::params::{BfCircuitParams, TapeMode};
+use bf_zk::prover;
+use bf_zk::prover::cache::KeyCache;
+use bf_zk::report;
+use halo2_proofs::halo2curves::bn256::Fr;
+use halo2curves::ff::PrimeField;
+use std::time::Instant;
+
+const USAGE: &str = "\
+usage:
+  bfprove <program.bf> [--input <text>] [--backend kzg|ipa] [--k <n>]
+                       [--bits <n>] [--max-cycles <n>] [--io lookup|evaluation]
+                       [--permutation product|lookup] [--one-hot]
+                       [--eof zero|max|unchanged] [--arithmetic wrapping|strict]
+                       [--tape <text>]
+                       [--report] [--layout <file.png>] [--cache <dir>]
+
+k defaults to the smallest one that fits the program and --max-cycles.
+--report only prints the circuit report, --layout (with the dev-graph
+feature) also renders the circuit layout. --cache keeps the KZG setup and
+keys in <dir> for the next runs of the same program with the same trace
+lengths. --eof is what `,` stores once the input is exhausted; reading past
+the input, with any --eof, needs --io evaluation. With --arithmetic strict, a
+program whose `+` or `-` would wrap a cell fails to run instead of being
+proven. --tape starts the program on the bytes of <text> and makes the initial
+and final values of the touched cells public, which needs --permutation
+product.";
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(arg: &str, value: String) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid value {} for {}", value, arg)))
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut positional = vec![];
+    let mut input = String::new();
+    let mut tape = None;
+    let mut backend = String::from("kzg");
+    let mut k = None;
+    let mut params = BfCircuitParams::default();
+    let mut report_only = false;
+    let mut layout = None;
+    let mut cache = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .unwrap_or_else(|| fail(format!("{} expects a value", arg)))
+        };
+        match arg.as_str() {
+            "--input" => input = value(),
+            "--tape" => tape = Some(value()),
+            "--backend" => backend = value(),
+            "--k" => k = Some(parse(arg, value())),
+            "--bits" => params.cell_bits = parse(arg, value()),
+            "--max-cycles" => params.max_cycles = parse(arg, value()),
+            "--io" => params.io = value().parse().unwrap_or_else(|e| fail(e)),
+            "--permutation" => params.permutation = value().parse().unwrap_or_else(|e| fail(e)),
+            "--one-hot" => params.one_hot = true,
+            "--eof" => params.eof = value().parse().unwrap_or_else(|e| fail(e)),
+            "--arithmetic" => params.arithmetic = value().parse().unwrap_or_else(|e| fail(e)),
+            "--report" => report_only = true,
+            "--layout" => layout = Some(value()),
+            "--cache" => cache = Some(KeyCache::new(value())),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    if tape.is_some() {
+        params.tape = TapeMode::Public;
+    }
+    params.check().unwrap_or_else(|e| fail(e));
+    let path = match &positional[..] {
+        [path] => path,
+        _ => fail("expected one program"),
+    };
+
+    let source = std::fs::read(path).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", path, e);
+        std::process::exit(1);
+    });
+    let program = code::try_compile(source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+    let k = k.unwrap_or_else(|| params.k(program.len()));
+
+    let mut vm = Interpreter::new();
+    vm.set_bits(params.cell_bits as u64);
+    vm.set_eof(params.eof);
+    vm.set_arithmetic(params.arithmetic);
+    vm.set_code(program);
+    vm.set_input(code::easygen(&input));
+    if let Some(tape) = &tape {
+        vm.set_tape(code::easygen(tape));
+    }
+    if let Err(trap) = vm.try_run() {
+        eprintln!("{}: {}", path, trap);
+        std::process::exit(1);
+    }
+    let usage = prover::Usage::of(&vm.matrix);
+    if usage.cycles > params.max_cycles as u64 {
+        eprintln!(
+            "{}: ran for {} cycles, more than --max-cycles {}",
+            path, usage.cycles, params.max_cycles
+        );
+        std::process::exit(1);
+    }
+    println!(
+        "{} cycles, {} cells, {:?}",
+        usage.cycles,
+        usage.max_memory_pointer + 1,
+        params
+    );
+    if params.tape == TapeMode::Public {
+        let tape = vm.matrix.final_tape();
+        println!(
+            "final tape: {:?}",
+            tape.iter().map(|v| v.get_lower_128()).collect::<Vec<_>>()
+        );
+    }
+
+    let circuit = VMCircuit::new(vm.matrix.clone(), params);
+    // the report is measured over BN254 whatever the backend
+    println!("{}", report::report(k, &circuit));
+    if let Some(path) = layout {
+        render_layout(k, &circuit, &path);
+    }
+    if report_only {
+        return;
+    }
+
+    match backend.as_str() {
+        "kzg" => {
+            let instances = instances(&vm.matrix, &params);
+            let start = Instant::now();
+            let (setup, pk) = match &cache {
+                Some(cache) => {
+                    let setup = cache.params(k).unwrap_or_else(|e| fail(e));
+                    let pk = cache
+                        .proving_key(&setup, &circuit)
+                        .unwrap_or_else(|e| fail(e));
+                    (setup, pk)
+                }
+                None => {
+                    let setup = prover::kzg::setup(k);
+                    let pk = prover::kzg::keygen(&setup, &circuit).expect("keygen failed");
+                    (setup, pk)
+                }
+            };
+            println!("keygen:  {:>10?}", start.elapsed());
+            let start = Instant::now();
+            let proof = prover::kzg::prove(&setup, &pk, circuit, &instances).expect("prove failed");
+            println!("prove:   {:>10?} ({} bytes)", start.elapsed(), proof.len());
+            let start = Instant::now();
+            prover::kzg::verify(&setup, pk.get_vk(), &proof, &instances).expect("verify failed");
+            println!("verify:  {:>10?}", start.elapsed());
+        }
+        "ipa" => {
+            let instances = instances(&vm.matrix, &params);
+            let circuit = VMCircuit::new(vm.matrix, params);
+            let setup = prover::ipa::setup(k);
+            let start = Instant::now();
+            let pk = prover::ipa::keygen(&setup, &circuit).expect("keygen failed");
+            println!("keygen:  {:>10?}", start.elapsed());
+            let start = Instant::now();
+            let proof = prover::ipa::prove(&setup, &pk, circuit, &instances).expect("prove failed");
+            println!("prove:   {:>10?} ({} bytes)", start.elapsed(), proof.len());
+            let start = Instant::now();
+            prover::ipa::verify(&setup, pk.get_vk(), &proof, &instances).expect("verify failed");
+            println!("verify:  {:>10?}", start.elapsed());
+        }
+        _ => fail(format!("unknown backend {}", backend)),
+    }
+}
+
+fn instances<F: PrimeField>(matrix: &Matrix, params: &BfCircuitParams) -> Vec<Vec<F>> {
+    let mut instances = prover::instances(matrix);
+    if params.tape == TapeMode::Public {
+        instances.extend(prover::tape_instances(matrix));
+    }
+    instances
+}
+
+#[cfg(feature = "dev-graph")]
+fn render_layout(k: u32, circuit: &VMCircuit<Fr>, path: &str) {
+    report::render_layout(k, circuit, path.as_ref()).unwrap_or_else(|e| {
+        eprintln!("failed to render {}: {}", path, e);
+        std::process::exit(1);
+    });
+}
+
+#[cfg(not(feature = "dev-graph"))]
+fn render_layout(_: u32, _: &VMCircuit<Fr>, _: &str) {
+    fail("--layout needs the dev-graph feature");
+}
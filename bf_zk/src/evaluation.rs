@@ -0,0 +1,124 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    plonk::{
+        Advice, Challenge, Column, ConstraintSystem, Error, Expression, Instance, SecondPhase,
+        Selector, VirtualCells,
+    },
+    poly::Rotation,
+};
+use halo2curves::ff::PrimeField;
+
+/// BrainSTARK's running evaluation: the values of the rows selected by an
+/// indicator, read as the coefficients of a polynomial evaluated at gamma:
+///
+/// e_0 = 0, e_{i+1} = ind_i * (gamma * e_i + v_i) + (1 - ind_i) * e_i.
+///
+/// Two tables hold the same sequence of selected values (with overwhelming
+/// probability over gamma) iff their final evaluations are equal. Unlike a
+/// lookup, this binds both the order and the number of values.
+#[derive(Debug, Clone)]
+pub struct RunningEvaluationConfig {
+    pub e: Column<Advice>,
+    pub s_first: Selector,
+    pub s_next: Selector,
+}
+
+impl RunningEvaluationConfig {
+    pub fn configure<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        name: &'static str,
+        gamma: Challenge,
+        indicator: impl FnOnce(&mut VirtualCells<'_, F>) -> Expression<F>,
+        value: impl FnOnce(&mut VirtualCells<'_, F>) -> Expression<F>,
+    ) -> Self {
+        let e = cs.advice_column_in(SecondPhase);
+        cs.enable_equality(e);
+        let s_first = cs.selector();
+        let s_next = cs.selector();
+
+        cs.create_gate(name, |meta| {
+            let one = Expression::Constant(F::from(1));
+            let gamma = meta.query_challenge(gamma);
+            let ind = indicator(meta);
+            let value = value(meta);
+            let s_first = meta.query_selector(s_first);
+            let s_next = meta.query_selector(s_next);
+            let cur_e = meta.query_advice(e, Rotation::cur());
+            let next_e = meta.query_advice(e, Rotation::next());
+
+            vec![
+                s_first * cur_e.clone(),
+                s_next
+                    * (next_e
+                        - ind.clone() * (gamma * cur_e.clone() + value)
+                        - (one - ind) * cur_e),
+            ]
+        });
+
+        RunningEvaluationConfig { e, s_first, s_next }
+    }
+
+    /// Assigns `terms.len() + 1` evaluation cells for the given
+    /// `(indicator, value)` terms and returns the cell holding the final
+    /// evaluation.
+    pub fn assign<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        terms: &[(F, F)],
+        gamma: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.s_first.enable(region, 0)?;
+        let mut e = Value::known(F::from(0));
+        let mut last = region.assign_advice(|| "running evaluation", self.e, 0, || e)?;
+        for (i, (ind, value)) in terms.iter().enumerate() {
+            self.s_next.enable(region, i)?;
+            if *ind != F::from(0) {
+                e = gamma * e + Value::known(*value);
+            }
+            last = region.assign_advice(|| "running evaluation", self.e, i + 1, || e)?;
+        }
+        Ok(last)
+    }
+}
+
+/// A public column together with the running evaluation of all of its values,
+/// the STARK-mode replacement of the output and input tables.
+#[derive(Debug, Clone)]
+pub struct InstanceEvaluation {
+    pub value: Column<Instance>,
+    pub evaluation: RunningEvaluationConfig,
+}
+
+impl InstanceEvaluation {
+    pub fn configure<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        name: &'static str,
+        gamma: Challenge,
+    ) -> Self {
+        let value = cs.instance_column();
+        let evaluation = RunningEvaluationConfig::configure(
+            cs,
+            name,
+            gamma,
+            |_| Expression::Constant(F::from(1)),
+            |meta| meta.query_instance(value, Rotation::cur()),
+        );
+        InstanceEvaluation { value, evaluation }
+    }
+
+    /// `values` are the public values, the region has to start at row 0 so
+    /// that the rows line up with the instance column.
+    pub fn load<F: PrimeField>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        name: &'static str,
+        values: &[F],
+        gamma: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let terms = values.iter().map(|v| (F::from(1), *v)).collect::<Vec<_>>();
+        layouter.assign_region(
+            || name,
+            |mut region| self.evaluation.assign(&mut region, &terms, gamma),
+        )
+    }
+}
@@ -0,0 +1,186 @@
+use bf_vm::dump;
+use bf_vm::interpreter::FieldExt;
+use bf_vm::matrix::Matrix;
+use halo2_proofs::dev::{FailureLocation, VerifyFailure};
+use std::fmt;
+
+/// A `VerifyFailure` translated back to the VM state of the cycle it concerns.
+#[derive(Debug, Clone)]
+pub struct FailureExplanation {
+    /// Gate or lookup name reported by the prover.
+    pub gate: String,
+    /// Constraint within the gate, for `ConstraintNotSatisfied`.
+    pub constraint: Option<usize>,
+    /// Which part of the gate was nonzero, e.g. `ip` for "instruction constraints".
+    pub subexpression: Option<&'static str>,
+    /// Region name and offset the failure was located at.
+    pub region: Option<String>,
+    pub offset: Option<usize>,
+    pub cycle: Option<usize>,
+    pub ip: Option<String>,
+    pub instruction: Option<String>,
+    pub mp: Option<String>,
+    pub mv: Option<String>,
+    /// The original failure, for anything that could not be decoded.
+    pub failure: String,
+}
+
+impl fmt::Display for FailureExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cycle {
+            Some(cycle) => write!(
+                f,
+                "cycle {}, ip {}, instruction '{}', mp {}, mv {}",
+                cycle,
+                self.ip.as_deref().unwrap_or("?"),
+                self.instruction.as_deref().unwrap_or("?"),
+                self.mp.as_deref().unwrap_or("?"),
+                self.mv.as_deref().unwrap_or("?"),
+            )?,
+            None => write!(f, "{}", self.failure)?,
+        }
+        write!(f, ": '{}'", self.gate)?;
+        if let Some(sub) = self.subexpression {
+            write!(f, " ({} constraint nonzero)", sub)?;
+        } else if let Some(index) = self.constraint {
+            write!(f, " (constraint {})", index)?;
+        }
+        if let (Some(region), Some(offset)) = (&self.region, self.offset) {
+            write!(f, " in {} at offset {}", region, offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the constraints of each gate, in the order they are returned by
+/// the gate closures.
+fn subexpression(gate: &str, index: usize) -> Option<&'static str> {
+    let names: &[&'static str] = match gate {
+        "instruction constraints" => &["ip", "mp", "mv"],
+        "invirant constrains" => &["clk", "mv_inv", "mv_inv"],
+        "boundary constraints" => &["clk", "ip", "mp", "mv"],
+        "mem gates" => &["mp step", "mv consistency", "new cell zero"],
+        "tape flags" => &["new cell"],
+        "tape edges" => &["edge flag"],
+        "is_zero" => &["mv is_zero"],
+        "eof flag" => &["eof boolean", "eof monotone"],
+        "input table next clk > clk" | "next clk > clk" => &["clk order"],
+        "instruction table gates" => &[
+            "ip step",
+            "ci consistency",
+            "ni consistency",
+            "operand consistency",
+        ],
+        "instruction table boundary" => &["ip"],
+        "running product" => &["first row", "transition"],
+        "opcode selectors" => &[
+            "SHL boolean",
+            "SHR boolean",
+            "ADD boolean",
+            "SUB boolean",
+            "GETCHAR boolean",
+            "PUTCHAR boolean",
+            "LB boolean",
+            "RB boolean",
+            "one selector set",
+            "ci recomposition",
+        ],
+        "processor output evaluation"
+        | "processor input evaluation"
+        | "output evaluation"
+        | "input evaluation"
+        | "initial tape evaluation"
+        | "final tape evaluation"
+        | "public initial tape"
+        | "public final tape" => &["first row", "transition"],
+        _ => &[],
+    };
+    names.get(index).copied()
+}
+
+/// Text between the last `('` and the last `')`, which is how halo2 prints
+/// gate and region names.
+fn quoted_name(s: &str) -> Option<String> {
+    let start = s.rfind("('")? + 2;
+    let end = s.rfind("')")?;
+    (start <= end).then(|| s[start..end].to_string())
+}
+
+fn constraint_index(s: &str) -> Option<usize> {
+    s.strip_prefix("Constraint ")?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Maps a row of a named region to the VM cycle it was generated at.
+fn cycle_of(matrix: &Matrix, region: &str, offset: usize) -> Option<usize> {
+    let cycle = match region {
+        "processor table" => return (offset < matrix.processor_matrix.len()).then_some(offset),
+        "mem table" => matrix.memory_matrix.get(offset)?.cycle,
+        "input table" => matrix.input_matrix.get(offset)?.cycle,
+        "output table" => matrix.output_matrix.get(offset)?.cycle,
+        _ => return None,
+    };
+    Some(cycle.get_lower_128() as usize)
+}
+
+pub fn explain_failure(failure: &VerifyFailure, matrix: &Matrix) -> FailureExplanation {
+    let (gate, constraint, location) = match failure {
+        VerifyFailure::ConstraintNotSatisfied {
+            constraint,
+            location,
+            ..
+        } => {
+            let constraint = constraint.to_string();
+            (
+                quoted_name(&constraint).unwrap_or_else(|| constraint.clone()),
+                constraint_index(&constraint),
+                Some(location),
+            )
+        }
+        VerifyFailure::Lookup { name, location, .. } => (name.to_string(), None, Some(location)),
+        VerifyFailure::Permutation { location, .. } => {
+            ("permutation".to_string(), None, Some(location))
+        }
+        _ => (failure.to_string(), None, None),
+    };
+
+    let (region, offset) = match location {
+        Some(FailureLocation::InRegion { region, offset }) => {
+            (quoted_name(&region.to_string()), Some(*offset))
+        }
+        Some(FailureLocation::OutsideRegion { row }) => (None, Some(*row)),
+        None => (None, None),
+    };
+
+    let cycle = match (&region, offset) {
+        (Some(region), Some(offset)) => cycle_of(matrix, region, offset),
+        _ => None,
+    };
+    let row = cycle.and_then(|c| matrix.processor_matrix.get(c));
+
+    FailureExplanation {
+        subexpression: constraint.and_then(|i| subexpression(&gate, i)),
+        gate,
+        constraint,
+        region,
+        offset,
+        cycle: row.and(cycle),
+        ip: row.map(|r| dump::value(&r.instruction_pointer)),
+        instruction: row.map(|r| dump::opcode(&r.current_instruction)),
+        mp: row.map(|r| dump::value(&r.memory_pointer)),
+        mv: row.map(|r| dump::value(&r.memory_value)),
+        failure: failure.to_string(),
+    }
+}
+
+/// Explains every failure returned by `MockProver::verify` in terms of the
+/// VM cycle, instruction and memory state it was raised at.
+pub fn explain_failures(failures: &[VerifyFailure], matrix: &Matrix) -> Vec<FailureExplanation> {
+    failures
+        .iter()
+        .map(|f| explain_failure(f, matrix))
+        .collect()
+}
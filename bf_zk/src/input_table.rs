@@ -1,13 +1,14 @@
+use crate::utilts::to_field;
 use bf_vm::{
     code::{GETCHAR, PUTCHAR},
     matrix::Matrix,
 };
 use halo2_proofs::{
     circuit::{layouter, Layouter, Value},
-    halo2curves::bn256::Fr,
     plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Instance, Selector},
     poly::Rotation,
 };
+use halo2curves::ff::PrimeField;
 
 #[derive(Debug, Clone)]
 pub struct InputTable {
@@ -18,7 +19,7 @@ pub struct InputTable {
 }
 
 impl InputTable {
-    pub fn configure(cs: &mut ConstraintSystem<Fr>) -> Self {
+    pub fn configure<F: PrimeField>(cs: &mut ConstraintSystem<F>) -> Self {
         let clk = cs.advice_column();
         let ci = cs.fixed_column();
         let value = cs.instance_column();
@@ -35,7 +36,7 @@ impl InputTable {
             let next_clk = meta.query_advice(clk, Rotation::next());
             let diff = meta.query_advice(diff, Rotation::next());
             let s = meta.query_selector(s_diff);
-            vec![s * (cur_clk + Expression::Constant(Fr::one()) + diff - next_clk)]
+            vec![s * (cur_clk + Expression::Constant(F::from(1)) + diff - next_clk)]
         });
 
         InputTable {
@@ -46,7 +47,11 @@ impl InputTable {
         }
     }
 
-    pub fn load(&mut self, mut layouter: impl Layouter<Fr>, matrix: &Matrix) -> Result<(), Error> {
+    pub fn load<F: PrimeField>(
+        &mut self,
+        mut layouter: impl Layouter<F>,
+        matrix: &Matrix,
+    ) -> Result<(), Error> {
         let input_mat = &matrix.input_matrix;
         layouter.assign_region(
             || "input table",
@@ -59,14 +64,14 @@ impl InputTable {
                         || "clk cell",
                         self.clk,
                         i,
-                        || Value::known(input_mat[i].cycle),
+                        || Value::known(to_field(&input_mat[i].cycle)),
                     )?;
 
                     region.assign_advice(
                         || "diff cell",
                         self.diff,
                         i,
-                        || Value::known(input_mat[i].diff),
+                        || Value::known(to_field(&input_mat[i].diff)),
                     )?;
                 }
                 Ok(())
@@ -0,0 +1,130 @@
+use crate::utilts::to_field;
+use bf_vm::matrix::Matrix;
+use halo2_proofs::{
+    circuit::{Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector},
+    poly::Rotation,
+};
+use halo2curves::ff::PrimeField;
+
+/// BrainSTARK's instruction table: the program rows and one row per executed
+/// cycle, sorted by instruction pointer. It sits between the program and the
+/// processor table: every program row must appear in it and every processor
+/// row is looked up in it, so the processor never reads the program directly.
+#[derive(Debug, Clone)]
+pub struct InstructionTable {
+    pub instruction_pointer: Column<Advice>,
+    pub current_instruction: Column<Advice>,
+    pub next_instruction: Column<Advice>,
+    pub operand: Column<Advice>,
+    pub s_first: Selector, //selector for the boundary constraint
+    pub s_i: Selector,     //selector for instruction table
+}
+
+impl InstructionTable {
+    pub fn configure<F: PrimeField>(cs: &mut ConstraintSystem<F>) -> Self {
+        let instruction_pointer = cs.advice_column();
+        let current_instruction = cs.advice_column();
+        let next_instruction = cs.advice_column();
+        let operand = cs.advice_column();
+        let s_first = cs.selector();
+        let s_i = cs.selector();
+
+        cs.enable_equality(instruction_pointer);
+        cs.enable_equality(current_instruction);
+        cs.enable_equality(next_instruction);
+        cs.enable_equality(operand);
+
+        let one = Expression::Constant(F::from(1));
+
+        cs.create_gate("instruction table boundary", |meta| {
+            let s_first = meta.query_selector(s_first);
+            let ip = meta.query_advice(instruction_pointer, Rotation::cur());
+            // ip_0 = 0
+            Constraints::with_selector(s_first, vec![ip])
+        });
+
+        cs.create_gate("instruction table gates", |meta| {
+            let si = meta.query_selector(s_i);
+            let cur_ip = meta.query_advice(instruction_pointer, Rotation::cur());
+            let next_ip = meta.query_advice(instruction_pointer, Rotation::next());
+            let cur_ci = meta.query_advice(current_instruction, Rotation::cur());
+            let next_ci = meta.query_advice(current_instruction, Rotation::next());
+            let cur_ni = meta.query_advice(next_instruction, Rotation::cur());
+            let next_ni = meta.query_advice(next_instruction, Rotation::next());
+            let cur_operand = meta.query_advice(operand, Rotation::cur());
+            let next_operand = meta.query_advice(operand, Rotation::next());
+
+            // I0: instruction pointer either increases by one or by zero
+            let i0 = (next_ip.clone() - cur_ip.clone() - one.clone())
+                * (next_ip.clone() - cur_ip.clone());
+            // I1, I2, I3: if the instruction pointer does not change, the
+            // current instruction, next instruction and operand stay the same
+            let i1 = (next_ip.clone() - cur_ip.clone() - one.clone()) * (next_ci - cur_ci);
+            let i2 = (next_ip.clone() - cur_ip.clone() - one.clone()) * (next_ni - cur_ni);
+            let i3 = (next_ip - cur_ip - one) * (next_operand - cur_operand);
+
+            Constraints::with_selector(si, vec![i0, i1, i2, i3])
+        });
+
+        InstructionTable {
+            instruction_pointer,
+            current_instruction,
+            next_instruction,
+            operand,
+            s_first,
+            s_i,
+        }
+    }
+
+    pub fn load<F: PrimeField>(
+        &mut self,
+        mut layouter: impl Layouter<F>,
+        matrix: &Matrix,
+    ) -> Result<(), Error> {
+        let instruction_mat = &matrix.instruction_matrix;
+        layouter.assign_region(
+            || "instruction table",
+            |mut region| {
+                for i in 0..instruction_mat.len() {
+                    if i == 0 {
+                        self.s_first.enable(&mut region, i)?;
+                    }
+                    if i < instruction_mat.len() - 1 {
+                        self.s_i.enable(&mut region, i)?;
+                    }
+
+                    region.assign_advice(
+                        || "instruction_pointer cell",
+                        self.instruction_pointer,
+                        i,
+                        || Value::known(to_field(&instruction_mat[i].instruction_pointer)),
+                    )?;
+
+                    region.assign_advice(
+                        || "current_instruction cell",
+                        self.current_instruction,
+                        i,
+                        || Value::known(to_field(&instruction_mat[i].current_instruction)),
+                    )?;
+
+                    region.assign_advice(
+                        || "next_instruction cell",
+                        self.next_instruction,
+                        i,
+                        || Value::known(to_field(&instruction_mat[i].next_instruction)),
+                    )?;
+
+                    region.assign_advice(
+                        || "operand cell",
+                        self.operand,
+                        i,
+                        || Value::known(to_field(&instruction_mat[i].operand)),
+                    )?;
+                }
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
+mod evaluation;
+pub mod explain;
 mod input_table;
+mod instruction_table;
 mod is_zero;
 pub mod main_config;
 mod memory_table;
 mod output_table;
+pub mod params;
+mod permutation;
 mod processor_table;
 mod program_table;
+pub mod prover;
 mod range_table;
+pub mod report;
 mod utilts;
@@ -1,144 +1,426 @@
+use crate::evaluation::InstanceEvaluation;
 use crate::input_table::InputTable;
+use crate::instruction_table::InstructionTable;
 use crate::memory_table::MemoryTable;
 use crate::output_table::OutputTable;
+use crate::params::{BfCircuitParams, IoMode, PermutationMode, TapeMode};
 use crate::processor_table::ProcessorTable;
 use crate::program_table::ProgramTable;
-use crate::utilts::PUTCHAR;
-use bf_vm::matrix::Matrix;
-use halo2_proofs::arithmetic::Field;
+use crate::utilts::{to_field, PUTCHAR};
+use bf_vm::matrix::{IOMatrixRow, Matrix};
 use halo2_proofs::{circuit::*, halo2curves::bn256::Fr, plonk::*, poly::Rotation};
+use halo2curves::ff::PrimeField;
 use std::marker::PhantomData;
 
+/// How the output and input tables are tied to the processor table.
 #[derive(Debug, Clone)]
-pub struct MainConfig<const RANGE: usize> {
+enum IoConfig {
+    /// (clk, ci, value) lookups into the processor table, with a range checked
+    /// clk difference to keep the I/O tables in execution order.
+    Lookup {
+        output_conf: OutputTable,
+        input_conf: InputTable,
+    },
+    /// BrainSTARK's running evaluations over the public values, which bind both
+    /// the order and the number of values.
+    Evaluation {
+        output_conf: InstanceEvaluation,
+        input_conf: InstanceEvaluation,
+        gamma: Challenge,
+    },
+}
+
+/// The tables and arguments selected by [`BfCircuitParams`].
+#[derive(Debug, Clone)]
+pub struct MainConfig<F: PrimeField> {
     program_conf: ProgramTable,
-    processor_conf: ProcessorTable<RANGE>,
+    instruction_conf: InstructionTable,
+    processor_conf: ProcessorTable<F>,
     mem_conf: MemoryTable,
-    output_conf: OutputTable,
-    input_conf: InputTable,
+    io_conf: IoConfig,
+    // public cycle count and highest memory pointer
+    usage: Column<Instance>,
+    // challenges of the memory running product
+    memory_challenges: Option<(Challenge, Challenge)>,
+    // public initial and final tapes, and their evaluation challenge
+    tape_conf: Option<(InstanceEvaluation, InstanceEvaluation, Challenge)>,
 }
 
-impl<const RANGE: usize> MainConfig<RANGE> {
-    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self {
+impl<F: PrimeField> MainConfig<F> {
+    fn configure(meta: &mut ConstraintSystem<F>, params: BfCircuitParams) -> Self {
+        // challenges for the processor/memory permutation argument
+        let memory_challenges =
+            (params.permutation == PermutationMode::RunningProduct).then(|| {
+                (
+                    meta.challenge_usable_after(FirstPhase),
+                    meta.challenge_usable_after(FirstPhase),
+                )
+            });
+        // challenge for the I/O evaluation arguments
+        let gamma =
+            (params.io == IoMode::Evaluation).then(|| meta.challenge_usable_after(FirstPhase));
+        // challenge for the tape evaluation arguments
+        let tape_gamma =
+            (params.tape == TapeMode::Public).then(|| meta.challenge_usable_after(FirstPhase));
+
         let program_conf = ProgramTable::configure(meta);
-        let processor_conf = ProcessorTable::configure(meta);
-        let mem_conf = MemoryTable::configure(meta);
-        let output_conf = OutputTable::configure(meta);
-        let input_conf = InputTable::configure(meta);
+        let instruction_conf = InstructionTable::configure(meta);
+        let processor_conf = ProcessorTable::configure(meta, &params, memory_challenges, gamma);
+        let mem_conf = MemoryTable::configure(meta, memory_challenges, tape_gamma);
+
+        if memory_challenges.is_none() {
+            // every memory row appears in the processor table
+            meta.lookup_any("memory lookup", |meta| {
+                let mem_clk = meta.query_advice(mem_conf.clk, Rotation::cur());
+                let processor_clk = meta.query_advice(processor_conf.clk, Rotation::cur());
+                let mem_mp = meta.query_advice(mem_conf.memory_pointer, Rotation::cur());
+                let processor_mp =
+                    meta.query_advice(processor_conf.memory_pointer, Rotation::cur());
+                let mem_mv = meta.query_advice(mem_conf.memory_value, Rotation::cur());
+                let processor_mv = meta.query_advice(processor_conf.memory_value, Rotation::cur());
+
+                vec![
+                    (mem_clk, processor_clk),
+                    (mem_mp, processor_mp),
+                    (mem_mv, processor_mv),
+                ]
+            });
+        }
 
+        // every program row appears in the instruction table
         meta.lookup_any("program lookup", |meta| {
-            let program_ci = meta.query_fixed(program_conf.current_instruction, Rotation::cur());
-            let processor_ci =
-                meta.query_advice(processor_conf.current_instruction, Rotation::cur());
             let program_ip = meta.query_fixed(program_conf.instruction_pointer, Rotation::cur());
-            let processor_ip =
-                meta.query_advice(processor_conf.instruction_pointer, Rotation::cur());
+            let program_ci = meta.query_fixed(program_conf.current_instruction, Rotation::cur());
             let program_ni = meta.query_fixed(program_conf.next_instruction, Rotation::cur());
-            let processor_ni = meta.query_advice(processor_conf.next_instruction, Rotation::cur());
+            let program_operand = meta.query_fixed(program_conf.operand, Rotation::cur());
+            let instruction_ip =
+                meta.query_advice(instruction_conf.instruction_pointer, Rotation::cur());
+            let instruction_ci =
+                meta.query_advice(instruction_conf.current_instruction, Rotation::cur());
+            let instruction_ni =
+                meta.query_advice(instruction_conf.next_instruction, Rotation::cur());
+            let instruction_operand = meta.query_advice(instruction_conf.operand, Rotation::cur());
 
             vec![
-                (program_ci, processor_ci),
-                (program_ip, processor_ip),
-                (program_ni, processor_ni),
+                (program_ip, instruction_ip),
+                (program_ci, instruction_ci),
+                (program_ni, instruction_ni),
+                (program_operand, instruction_operand),
             ]
         });
 
-        meta.lookup_any("memory lookup", |meta| {
-            let mem_clk = meta.query_advice(mem_conf.clk, Rotation::cur());
-            let processor_clk = meta.query_advice(processor_conf.clk, Rotation::cur());
-            let mem_mp = meta.query_advice(mem_conf.memory_pointer, Rotation::cur());
-            let processor_mp = meta.query_advice(processor_conf.memory_pointer, Rotation::cur());
-            let mem_mv = meta.query_advice(mem_conf.memory_value, Rotation::cur());
-            let processor_mv = meta.query_advice(processor_conf.memory_value, Rotation::cur());
+        // every instruction row appears in the program table, so the prover
+        // cannot append instructions past the end of the program
+        meta.lookup_any("instruction program lookup", |meta| {
+            let instruction_ip =
+                meta.query_advice(instruction_conf.instruction_pointer, Rotation::cur());
+            let instruction_ci =
+                meta.query_advice(instruction_conf.current_instruction, Rotation::cur());
+            let instruction_ni =
+                meta.query_advice(instruction_conf.next_instruction, Rotation::cur());
+            let instruction_operand = meta.query_advice(instruction_conf.operand, Rotation::cur());
+            let program_ip = meta.query_fixed(program_conf.instruction_pointer, Rotation::cur());
+            let program_ci = meta.query_fixed(program_conf.current_instruction, Rotation::cur());
+            let program_ni = meta.query_fixed(program_conf.next_instruction, Rotation::cur());
+            let program_operand = meta.query_fixed(program_conf.operand, Rotation::cur());
 
             vec![
-                (mem_clk, processor_clk),
-                (mem_mp, processor_mp),
-                (mem_mv, processor_mv),
+                (instruction_ip, program_ip),
+                (instruction_ci, program_ci),
+                (instruction_ni, program_ni),
+                (instruction_operand, program_operand),
             ]
         });
 
-        meta.lookup_any("output lookup", |meta| {
-            let processor_clk = meta.query_advice(processor_conf.clk, Rotation::cur());
-            let output_clk = meta.query_advice(output_conf.clk, Rotation::cur());
+        // every executed instruction is consistent with the instruction table
+        meta.lookup_any("instruction lookup", |meta| {
+            let processor_ip =
+                meta.query_advice(processor_conf.instruction_pointer, Rotation::cur());
             let processor_ci =
                 meta.query_advice(processor_conf.current_instruction, Rotation::cur());
-            let output_ci = meta.query_fixed(output_conf.ci, Rotation::cur());
-            let processor_mv = meta.query_advice(processor_conf.memory_value, Rotation::cur());
-            let output_val = meta.query_instance(output_conf.value, Rotation::cur());
+            let processor_ni = meta.query_advice(processor_conf.next_instruction, Rotation::cur());
+            let processor_operand = meta.query_advice(processor_conf.operand, Rotation::cur());
+            let instruction_ip =
+                meta.query_advice(instruction_conf.instruction_pointer, Rotation::cur());
+            let instruction_ci =
+                meta.query_advice(instruction_conf.current_instruction, Rotation::cur());
+            let instruction_ni =
+                meta.query_advice(instruction_conf.next_instruction, Rotation::cur());
+            let instruction_operand = meta.query_advice(instruction_conf.operand, Rotation::cur());
+
             vec![
-                (output_clk, processor_clk),
-                (output_ci, processor_ci),
-                (output_val, processor_mv),
+                (processor_ip, instruction_ip),
+                (processor_ci, instruction_ci),
+                (processor_ni, instruction_ni),
+                (processor_operand, instruction_operand),
             ]
         });
 
-        meta.lookup_any("input lookup", |meta| {
-            let processor_clk = meta.query_advice(processor_conf.clk, Rotation::cur());
-            let input_clk = meta.query_advice(input_conf.clk, Rotation::cur());
-            let processor_mv = meta.query_advice(processor_conf.memory_value, Rotation::cur());
-            let input_val = meta.query_instance(input_conf.value, Rotation::cur());
-            vec![(input_clk, processor_clk), (input_val, processor_mv)]
-        });
+        let io_conf = match gamma {
+            Some(gamma) => IoConfig::Evaluation {
+                output_conf: InstanceEvaluation::configure(meta, "output evaluation", gamma),
+                input_conf: InstanceEvaluation::configure(meta, "input evaluation", gamma),
+                gamma,
+            },
+            None => {
+                let output_conf = OutputTable::configure(meta);
+                let input_conf = InputTable::configure(meta);
 
-        meta.lookup_any("Range-Check: diff in output are within 0-255", |meta| {
-            let diff = meta.query_advice(output_conf.diff, Rotation::cur());
-            let range_val = meta.query_fixed(processor_conf.range_config.table, Rotation::cur());
-            vec![(diff, range_val)]
-        });
+                meta.lookup_any("output lookup", |meta| {
+                    let processor_clk = meta.query_advice(processor_conf.clk, Rotation::cur());
+                    let output_clk = meta.query_advice(output_conf.clk, Rotation::cur());
+                    let processor_ci =
+                        meta.query_advice(processor_conf.current_instruction, Rotation::cur());
+                    let output_ci = meta.query_fixed(output_conf.ci, Rotation::cur());
+                    let processor_mv =
+                        meta.query_advice(processor_conf.memory_value, Rotation::cur());
+                    let output_val = meta.query_instance(output_conf.value, Rotation::cur());
+                    vec![
+                        (output_clk, processor_clk),
+                        (output_ci, processor_ci),
+                        (output_val, processor_mv),
+                    ]
+                });
+
+                meta.lookup_any("input lookup", |meta| {
+                    let processor_clk = meta.query_advice(processor_conf.clk, Rotation::cur());
+                    let input_clk = meta.query_advice(input_conf.clk, Rotation::cur());
+                    let processor_mv =
+                        meta.query_advice(processor_conf.memory_value, Rotation::cur());
+                    let input_val = meta.query_instance(input_conf.value, Rotation::cur());
+                    vec![(input_clk, processor_clk), (input_val, processor_mv)]
+                });
 
-        meta.lookup_any("Range-Check: diff in input are within 0-255", |meta| {
-            let diff = meta.query_advice(input_conf.diff, Rotation::cur());
-            let range_val = meta.query_fixed(processor_conf.range_config.table, Rotation::cur());
-            vec![(diff, range_val)]
+                meta.lookup_any("Range-Check: diff in output are within 0-255", |meta| {
+                    let diff = meta.query_advice(output_conf.diff, Rotation::cur());
+                    let range_val =
+                        meta.query_fixed(processor_conf.range_config.table, Rotation::cur());
+                    vec![(diff, range_val)]
+                });
+
+                meta.lookup_any("Range-Check: diff in input are within 0-255", |meta| {
+                    let diff = meta.query_advice(input_conf.diff, Rotation::cur());
+                    let range_val =
+                        meta.query_fixed(processor_conf.range_config.table, Rotation::cur());
+                    vec![(diff, range_val)]
+                });
+
+                IoConfig::Lookup {
+                    output_conf,
+                    input_conf,
+                }
+            }
+        };
+
+        // instance column 2: cycles, then the highest memory pointer
+        let usage = meta.instance_column();
+        meta.enable_equality(usage);
+
+        // after the usage column, so that the tapes are instance columns 3 and 4
+        let tape_conf = tape_gamma.map(|gamma| {
+            (
+                InstanceEvaluation::configure(meta, "public initial tape", gamma),
+                InstanceEvaluation::configure(meta, "public final tape", gamma),
+                gamma,
+            )
         });
 
         MainConfig {
             program_conf,
+            instruction_conf,
             processor_conf,
             mem_conf,
-            output_conf,
-            input_conf,
+            io_conf,
+            usage,
+            memory_challenges,
+            tape_conf,
         }
     }
 
-    fn assign(&mut self, mut layouter: impl Layouter<Fr>, matrix: &Matrix) -> Result<(), Error> {
+    fn assign(&mut self, mut layouter: impl Layouter<F>, matrix: &Matrix) -> Result<(), Error> {
         self.program_conf
             .load(layouter.namespace(|| "program layouter"), matrix)?;
-        self.processor_conf
-            .load(layouter.namespace(|| "processor layouter"), matrix)?;
-        self.mem_conf
-            .load(layouter.namespace(|| "memory layouter"), matrix)?;
-        self.output_conf
-            .load(layouter.namespace(|| "output layouter"), matrix)?;
-        self.input_conf
-            .load(layouter.namespace(|| "input layouter"), matrix)?;
+        self.instruction_conf
+            .load(layouter.namespace(|| "instruction layouter"), matrix)?;
+        let (alpha, beta) = match self.memory_challenges {
+            Some((alpha, beta)) => (layouter.get_challenge(alpha), layouter.get_challenge(beta)),
+            None => (Value::unknown(), Value::unknown()),
+        };
+        let gamma = match &self.io_conf {
+            IoConfig::Evaluation { gamma, .. } => layouter.get_challenge(*gamma),
+            IoConfig::Lookup { .. } => Value::unknown(),
+        };
+        let processor = self.processor_conf.load(
+            layouter.namespace(|| "processor layouter"),
+            matrix,
+            alpha,
+            beta,
+            gamma,
+        )?;
+        let tape_gamma = match &self.tape_conf {
+            Some((_, _, gamma)) => layouter.get_challenge(*gamma),
+            None => Value::unknown(),
+        };
+        let memory = self.mem_conf.load(
+            layouter.namespace(|| "memory layouter"),
+            matrix,
+            alpha,
+            beta,
+            tape_gamma,
+        )?;
+        // clk increases by one from 0 and mp by zero or one in the sorted memory
+        // table, so their last values are the cycle count and the highest mp.
+        // The latter only holds if the memory table is a permutation of the
+        // processor rows, the memory lookup lets rows be dropped
+        if let Some(cycles) = &processor.cycles {
+            layouter.constrain_instance(cycles.cell(), self.usage, 0)?;
+        }
+        if let Some(mp) = &memory.max_memory_pointer {
+            layouter.constrain_instance(mp.cell(), self.usage, 1)?;
+        }
+        // the memory table is a permutation of the (clk, mp, mv) processor rows
+        if let (Some(processor_product), Some(memory_product)) =
+            (&processor.memory_product, &memory.processor_product)
+        {
+            layouter.assign_region(
+                || "memory permutation",
+                |mut region| {
+                    region.constrain_equal(processor_product.cell(), memory_product.cell())
+                },
+            )?;
+        }
+
+        match &mut self.io_conf {
+            IoConfig::Lookup {
+                output_conf,
+                input_conf,
+            } => {
+                output_conf.load(layouter.namespace(|| "output layouter"), matrix)?;
+                input_conf.load(layouter.namespace(|| "input layouter"), matrix)?;
+            }
+            IoConfig::Evaluation {
+                output_conf,
+                input_conf,
+                ..
+            } => {
+                let values = |rows: &[IOMatrixRow]| {
+                    rows.iter().map(|r| to_field(&r.value)).collect::<Vec<_>>()
+                };
+                let output = output_conf.load(
+                    layouter.namespace(|| "output layouter"),
+                    "output evaluation",
+                    &values(&matrix.output_matrix),
+                    gamma,
+                )?;
+                let input = input_conf.load(
+                    layouter.namespace(|| "input layouter"),
+                    "input evaluation",
+                    &values(&matrix.input_matrix),
+                    gamma,
+                )?;
+                // the public values are exactly the PUTCHAR/GETCHAR values, in order
+                layouter.assign_region(
+                    || "io evaluation",
+                    |mut region| {
+                        region.constrain_equal(
+                            processor.output_evaluation.as_ref().unwrap().cell(),
+                            output.cell(),
+                        )?;
+                        region.constrain_equal(
+                            processor.input_evaluation.as_ref().unwrap().cell(),
+                            input.cell(),
+                        )
+                    },
+                )?;
+            }
+        }
+
+        if let Some((initial_conf, final_conf, _)) = &self.tape_conf {
+            let values = |tape: Vec<Fr>| tape.iter().map(to_field).collect::<Vec<_>>();
+            let initial = initial_conf.load(
+                layouter.namespace(|| "initial tape layouter"),
+                "public initial tape",
+                &values(matrix.initial_tape()),
+                tape_gamma,
+            )?;
+            let final_ = final_conf.load(
+                layouter.namespace(|| "final tape layouter"),
+                "public final tape",
+                &values(matrix.final_tape()),
+                tape_gamma,
+            )?;
+            // the public tapes are exactly the first and last values of the cells
+            layouter.assign_region(
+                || "tape evaluation",
+                |mut region| {
+                    region.constrain_equal(
+                        memory.initial_tape.as_ref().unwrap().cell(),
+                        initial.cell(),
+                    )?;
+                    region
+                        .constrain_equal(memory.final_tape.as_ref().unwrap().cell(), final_.cell())
+                },
+            )?;
+        }
         Ok(())
     }
 }
 
+/// Version of the constraint system, part of the key of cached proving keys:
+/// bump it whenever a change to the tables changes the keys.
+pub const CIRCUIT_VERSION: u32 = 4;
+
 #[derive(Default)]
-pub struct VMCircuit<F: Field, const RANGE: usize> {
+pub struct VMCircuit<F: PrimeField> {
     pub matrix: Matrix,
+    pub params: BfCircuitParams,
     pub _marker: PhantomData<F>,
 }
 
-impl<const RANGE: usize> Circuit<Fr> for VMCircuit<Fr, RANGE> {
-    type Config = MainConfig<RANGE>;
+impl<F: PrimeField> VMCircuit<F> {
+    pub fn new(matrix: Matrix, params: BfCircuitParams) -> Self {
+        VMCircuit {
+            matrix,
+            params,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for VMCircuit<F> {
+    type Config = MainConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
+    type Params = BfCircuitParams;
 
     fn without_witnesses(&self) -> Self {
-        VMCircuit::default()
+        VMCircuit::new(Matrix::default(), self.params)
     }
-    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
-        MainConfig::configure(meta)
+
+    fn params(&self) -> Self::Params {
+        self.params
+    }
+
+    fn configure_with_params(meta: &mut ConstraintSystem<F>, params: Self::Params) -> Self::Config {
+        MainConfig::configure(meta, params)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        MainConfig::configure(meta, BfCircuitParams::default())
     }
 
     fn synthesize(
         &self,
         mut config: Self::Config,
-        layouter: impl Layouter<Fr>,
+        layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
+        if self.params.check().is_err() {
+            return Err(Error::Synthesis);
+        }
+        // the processor table has one row per cycle and one for the final
+        // state. A prover-side check only, `configure` does not see max_cycles
+        if self.matrix.processor_matrix.len() > self.params.max_cycles + 1 {
+            return Err(Error::Synthesis);
+        }
         config.assign(layouter, &self.matrix)?;
         Ok(())
     }
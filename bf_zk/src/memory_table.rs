@@ -1,12 +1,16 @@
+use crate::evaluation::RunningEvaluationConfig;
+use crate::permutation::RunningProductConfig;
+use crate::utilts::to_field;
 use bf_vm::matrix::Matrix;
 use halo2_proofs::{
-    circuit::{Layouter, Value},
-    halo2curves::bn256::Fr,
+    circuit::{AssignedCell, Layouter, Value},
     plonk::{
-        Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector, TableColumn,
+        Advice, Challenge, Column, ConstraintSystem, Constraints, Error, Expression, Selector,
+        TableColumn,
     },
     poly::Rotation,
 };
+use halo2curves::ff::PrimeField;
 
 #[derive(Debug, Clone)]
 pub struct MemoryTable {
@@ -14,21 +18,117 @@ pub struct MemoryTable {
     pub memory_pointer: Column<Advice>,
     pub memory_value: Column<Advice>,
     pub s_m: Selector,
+    // first row, where mp is 0
+    pub s_first: Selector,
+    // permutation with the processor table, only in the running product mode
+    pub processor_product: Option<RunningProductConfig>,
+    // initial and final tape evaluations, only in the public tape mode
+    pub tape: Option<MemoryTape>,
+}
+
+/// Running evaluations of the first and the last value of every cell, in
+/// cell order. `new_cell` is 1 on the first row of a cell and on a sentinel
+/// row past the end of the table, so a row is the last of its cell iff the
+/// next row has the flag.
+#[derive(Debug, Clone)]
+pub struct MemoryTape {
+    pub new_cell: Column<Advice>,
+    // first row and sentinel row
+    pub s_edge: Selector,
+    pub initial: RunningEvaluationConfig,
+    pub final_: RunningEvaluationConfig,
+}
+
+/// Final values of the running arguments of the memory table.
+pub struct MemoryArguments<F: PrimeField> {
+    /// mp of the last row, the highest cell the program touched
+    pub max_memory_pointer: Option<AssignedCell<F, F>>,
+    pub processor_product: Option<AssignedCell<F, F>>,
+    pub initial_tape: Option<AssignedCell<F, F>>,
+    pub final_tape: Option<AssignedCell<F, F>>,
 }
 
 impl MemoryTable {
-    pub fn configure(cs: &mut ConstraintSystem<Fr>) -> Self {
+    /// With `tape_gamma`, new cells may start with any value, and the table
+    /// computes the running evaluations of the initial and final tape instead.
+    pub fn configure<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        challenges: Option<(Challenge, Challenge)>,
+        tape_gamma: Option<Challenge>,
+    ) -> Self {
         let clk = cs.advice_column();
         let memory_pointer = cs.advice_column();
         let memory_value = cs.advice_column();
         let s_m = cs.selector();
+        let s_first = cs.selector();
 
         cs.enable_equality(clk);
         cs.enable_equality(memory_pointer);
         cs.enable_equality(memory_value);
 
-        let one = Expression::Constant(Fr::one());
+        let processor_product = challenges.map(|(alpha, beta)| {
+            RunningProductConfig::configure(
+                cs,
+                vec![clk, memory_pointer, memory_value],
+                alpha,
+                beta,
+            )
+        });
+
+        let tape = tape_gamma.map(|gamma| {
+            let new_cell = cs.advice_column();
+            let s_edge = cs.selector();
+            let initial = RunningEvaluationConfig::configure(
+                cs,
+                "initial tape evaluation",
+                gamma,
+                |meta| meta.query_advice(new_cell, Rotation::cur()),
+                |meta| meta.query_advice(memory_value, Rotation::cur()),
+            );
+            let final_ = RunningEvaluationConfig::configure(
+                cs,
+                "final tape evaluation",
+                gamma,
+                |meta| meta.query_advice(new_cell, Rotation::next()),
+                |meta| meta.query_advice(memory_value, Rotation::cur()),
+            );
+            MemoryTape {
+                new_cell,
+                s_edge,
+                initial,
+                final_,
+            }
+        });
+
+        let one = Expression::Constant(F::from(1));
+
+        if let Some(tape) = &tape {
+            let (new_cell, s_edge) = (tape.new_cell, tape.s_edge);
+            cs.create_gate("tape flags", |meta| {
+                let sm = meta.query_selector(s_m);
+                let cur_mp = meta.query_advice(memory_pointer, Rotation::cur());
+                let next_mp = meta.query_advice(memory_pointer, Rotation::next());
+                let next_new_cell = meta.query_advice(new_cell, Rotation::next());
+                // a cell starts where mp increases
+                Constraints::with_selector(sm, vec![next_new_cell - (next_mp - cur_mp)])
+            });
+            cs.create_gate("tape edges", |meta| {
+                let s_edge = meta.query_selector(s_edge);
+                let new_cell = meta.query_advice(new_cell, Rotation::cur());
+                // the first row starts a cell, the sentinel row closes the last one
+                Constraints::with_selector(s_edge, vec![new_cell - one.clone()])
+            });
+        }
+
+        cs.create_gate("mem boundary", |meta| {
+            let s_first = meta.query_selector(s_first);
+            let mp = meta.query_advice(memory_pointer, Rotation::cur());
+            // the sorted table starts at cell 0, so no row of the processor
+            // table can move left of it
+            Constraints::with_selector(s_first, vec![mp])
+        });
 
+        let zero_new_cells = tape.is_none();
         cs.create_gate("mem gates", |meta| {
             let sm = meta.query_selector(s_m);
             let cur_mp = meta.query_advice(memory_pointer, Rotation::cur());
@@ -41,15 +141,19 @@ impl MemoryTable {
             // M0: memory pointer either increase by one or by zero
             let m0 = (next_mp.clone() - cur_mp.clone() - one.clone())
                 * (next_mp.clone() - cur_mp.clone());
-            // M1: If mp increases by 1, then mv must be set to zero
-            let m1 = (next_mp.clone() - cur_mp.clone()) * next_mv.clone();
             // M2: when the memory pointer does not change:
             // a) memory value remains the same or b) the cycle count only increases by one
             let m2 = (next_mp.clone() - cur_mp.clone() - one.clone())
                 * (cur_mv.clone() - next_mv.clone())
                 * (next_clk - cur_clk.clone() - one.clone());
+            let mut constraints = vec![m0, m2];
+            // M1: If mp increases by 1, then mv must be set to zero
+            // (unless the initial tape is public)
+            if zero_new_cells {
+                constraints.push((next_mp - cur_mp) * next_mv);
+            }
 
-            Constraints::with_selector(sm, vec![m0, m1, m2])
+            Constraints::with_selector(sm, constraints)
         });
 
         MemoryTable {
@@ -57,14 +161,31 @@ impl MemoryTable {
             memory_pointer,
             memory_value,
             s_m,
+            s_first,
+            processor_product,
+            tape,
         }
     }
-    pub fn load(&mut self, mut layouter: impl Layouter<Fr>, matrix: &Matrix) -> Result<(), Error> {
+
+    /// Assigns the memory table and returns the final values of its running
+    /// arguments.
+    pub fn load<F: PrimeField>(
+        &mut self,
+        mut layouter: impl Layouter<F>,
+        matrix: &Matrix,
+        alpha: Value<F>,
+        beta: Value<F>,
+        tape_gamma: Value<F>,
+    ) -> Result<MemoryArguments<F>, Error> {
         let mem_mat = &matrix.memory_matrix;
-        layouter.assign_region(
+        let arguments = layouter.assign_region(
             || "mem table",
             |mut region| {
+                let mut max_memory_pointer = None;
                 for i in 0..mem_mat.len() {
+                    if i == 0 {
+                        self.s_first.enable(&mut region, i)?;
+                    }
                     if i < mem_mat.len() - 1 {
                         self.s_m.enable(&mut region, i)?;
                     }
@@ -72,24 +193,87 @@ impl MemoryTable {
                         || "clk cell",
                         self.clk,
                         i,
-                        || Value::known(mem_mat[i].cycle),
+                        || Value::known(to_field(&mem_mat[i].cycle)),
                     )?;
-                    region.assign_advice(
+                    let mp = region.assign_advice(
                         || "mem pointer cell",
                         self.memory_pointer,
                         i,
-                        || Value::known(mem_mat[i].memory_pointer),
+                        || Value::known(to_field(&mem_mat[i].memory_pointer)),
                     )?;
+                    max_memory_pointer = Some(mp);
                     region.assign_advice(
                         || "mem value cell",
                         self.memory_value,
                         i,
-                        || Value::known(mem_mat[i].memory_value),
+                        || Value::known(to_field(&mem_mat[i].memory_value)),
                     )?;
                 }
-                Ok(())
+
+                let rows = mem_mat
+                    .iter()
+                    .map(|r| {
+                        [r.cycle, r.memory_pointer, r.memory_value]
+                            .iter()
+                            .map(to_field::<F>)
+                            .collect()
+                    })
+                    .collect::<Vec<_>>();
+                let processor_product = match &self.processor_product {
+                    Some(config) => Some(config.assign(&mut region, &rows, alpha, beta)?),
+                    None => None,
+                };
+
+                let (initial_tape, final_tape) = match &self.tape {
+                    Some(tape) => {
+                        // the flag of every row, then the sentinel
+                        let new_cell = (0..=mem_mat.len())
+                            .map(|i| {
+                                i == 0
+                                    || i == mem_mat.len()
+                                    || mem_mat[i].memory_pointer != mem_mat[i - 1].memory_pointer
+                            })
+                            .collect::<Vec<_>>();
+                        for (i, flag) in new_cell.iter().enumerate() {
+                            if i == 0 || i == mem_mat.len() {
+                                tape.s_edge.enable(&mut region, i)?;
+                            }
+                            region.assign_advice(
+                                || "new cell flag",
+                                tape.new_cell,
+                                i,
+                                || Value::known(F::from(*flag as u64)),
+                            )?;
+                        }
+                        let terms = |last: bool| {
+                            mem_mat
+                                .iter()
+                                .enumerate()
+                                .map(|(i, r)| {
+                                    let flag = new_cell[if last { i + 1 } else { i }];
+                                    (F::from(flag as u64), to_field(&r.memory_value))
+                                })
+                                .collect::<Vec<_>>()
+                        };
+                        (
+                            Some(
+                                tape.initial
+                                    .assign(&mut region, &terms(false), tape_gamma)?,
+                            ),
+                            Some(tape.final_.assign(&mut region, &terms(true), tape_gamma)?),
+                        )
+                    }
+                    None => (None, None),
+                };
+
+                Ok(MemoryArguments {
+                    max_memory_pointer,
+                    processor_product,
+                    initial_tape,
+                    final_tape,
+                })
             },
         )?;
-        Ok(())
+        Ok(arguments)
     }
 }
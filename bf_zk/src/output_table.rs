@@ -1,10 +1,11 @@
+use crate::utilts::to_field;
 use bf_vm::{code::PUTCHAR, matrix::Matrix};
 use halo2_proofs::{
     circuit::{layouter, Layouter, Value},
-    halo2curves::bn256::Fr,
     plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Instance, Selector},
     poly::Rotation,
 };
+use halo2curves::ff::PrimeField;
 
 #[derive(Debug, Clone)]
 pub struct OutputTable {
@@ -16,7 +17,7 @@ pub struct OutputTable {
 }
 
 impl OutputTable {
-    pub fn configure(cs: &mut ConstraintSystem<Fr>) -> Self {
+    pub fn configure<F: PrimeField>(cs: &mut ConstraintSystem<F>) -> Self {
         let clk = cs.advice_column();
         let ci = cs.fixed_column();
         let value = cs.instance_column();
@@ -33,7 +34,7 @@ impl OutputTable {
             let next_clk = meta.query_advice(clk, Rotation::next());
             let diff = meta.query_advice(diff, Rotation::next());
             let s = meta.query_selector(s_diff);
-            vec![s * (cur_clk + Expression::Constant(Fr::one()) + diff - next_clk)]
+            vec![s * (cur_clk + Expression::Constant(F::from(1)) + diff - next_clk)]
         });
 
         OutputTable {
@@ -45,7 +46,11 @@ impl OutputTable {
         }
     }
 
-    pub fn load(&mut self, mut layouter: impl Layouter<Fr>, matrix: &Matrix) -> Result<(), Error> {
+    pub fn load<F: PrimeField>(
+        &mut self,
+        mut layouter: impl Layouter<F>,
+        matrix: &Matrix,
+    ) -> Result<(), Error> {
         let output_mat = &matrix.output_matrix;
         layouter.assign_region(
             || "output table",
@@ -58,21 +63,21 @@ impl OutputTable {
                         || "clk cell",
                         self.clk,
                         i,
-                        || Value::known(output_mat[i].cycle),
+                        || Value::known(to_field(&output_mat[i].cycle)),
                     )?;
 
                     region.assign_fixed(
                         || "ci cell",
                         self.ci,
                         i,
-                        || Value::known(Fr::from(PUTCHAR as u64)),
+                        || Value::known(F::from(PUTCHAR as u64)),
                     )?;
 
                     region.assign_advice(
                         || "diff cell",
                         self.diff,
                         i,
-                        || Value::known(output_mat[i].diff),
+                        || Value::known(to_field(&output_mat[i].diff)),
                     )?;
                 }
                 Ok(())
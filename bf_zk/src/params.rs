@@ -0,0 +1,178 @@
+//! Runtime shape of the VM circuit, passed to `configure` through halo2's
+//! circuit params so that one binary can prove with any of them.
+pub use bf_vm::interpreter::{Arithmetic, EofPolicy};
+use bf_vm::sizing::k_for;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the output and input tables are tied to the processor table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IoMode {
+    /// (clk, ci, value) lookups into the processor table.
+    #[default]
+    Lookup,
+    /// BrainSTARK's running evaluations, which also bind the order and the
+    /// number of values.
+    Evaluation,
+}
+
+/// How the memory table is tied to the processor table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PermutationMode {
+    /// Challenge-based running product: the memory table is a permutation of
+    /// the processor rows.
+    #[default]
+    RunningProduct,
+    /// A single `lookup_any`, which only proves that every memory row appears
+    /// in the processor table. Cheaper, but a prover may drop or duplicate
+    /// memory rows, which leaves the public highest memory pointer unbound:
+    /// `BfCircuitParams::check` rejects it.
+    Lookup,
+}
+
+/// What the circuit says about the tape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TapeMode {
+    /// The tape starts zeroed and its final state is not exposed.
+    #[default]
+    Zero,
+    /// The initial and final values of the touched cells are public, in
+    /// instance columns 3 and 4 (see `Matrix::initial_tape`/`final_tape`), so
+    /// a program can prove a transition of a data tape. The tapes are exposed
+    /// raw, not as a commitment or hash: the instance grows with the number
+    /// of touched cells, and a verifier learns every value of both tapes.
+    /// Needs `PermutationMode::RunningProduct`.
+    Public,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BfCircuitParams {
+    /// Width of a tape cell, the range table has `2^cell_bits` rows.
+    pub cell_bits: usize,
+    /// Largest number of executed instructions, used to size `k`. Only the
+    /// prover checks it: the constraints do not depend on it, so the
+    /// verifying key does not bind it and a verifier has to bound the public
+    /// cycle count (`prover::Usage`) instead.
+    pub max_cycles: usize,
+    pub io: IoMode,
+    pub permutation: PermutationMode,
+    /// Decode opcodes with boolean selector columns instead of deselector
+    /// polynomials.
+    pub one_hot: bool,
+    /// What `,` stores once the input is exhausted, must match the
+    /// interpreter that produced the trace. Any policy but the default needs
+    /// `IoMode::Evaluation`, see [`BfCircuitParams::check`].
+    pub eof: EofPolicy,
+    /// With `Arithmetic::Strict`, `+` and `-` may not wrap, so a trace
+    /// that overflows a cell does not satisfy the circuit.
+    pub arithmetic: Arithmetic,
+    pub tape: TapeMode,
+}
+
+impl Default for BfCircuitParams {
+    fn default() -> Self {
+        BfCircuitParams {
+            cell_bits: 8,
+            max_cycles: 1 << 16,
+            io: IoMode::default(),
+            permutation: PermutationMode::default(),
+            one_hot: false,
+            eof: EofPolicy::default(),
+            arithmetic: Arithmetic::default(),
+            tape: TapeMode::default(),
+        }
+    }
+}
+
+impl BfCircuitParams {
+    /// Smallest `k` that fits a program of `program_len` instructions running
+    /// for `max_cycles` cycles, see [`k_for`].
+    pub fn k(&self, program_len: usize) -> u32 {
+        k_for(program_len, self.max_cycles as u64, self.cell_bits as u64)
+    }
+
+    /// Rejects the combinations the circuit would not prove soundly. Only the
+    /// input evaluation ties the `eof` flag to the number of public inputs,
+    /// so the lookup I/O mode pins the flag to 0 and an EOF policy other
+    /// than the default would never apply.
+    pub fn check(&self) -> Result<(), String> {
+        if self.eof != EofPolicy::default() && self.io != IoMode::Evaluation {
+            return Err(format!(
+                "eof policy {} needs the evaluation I/O mode",
+                self.eof
+            ));
+        }
+        // dropped memory rows would let the prover pick the final tape
+        if self.tape == TapeMode::Public && self.permutation != PermutationMode::RunningProduct {
+            return Err("the public tape needs the product permutation".to_string());
+        }
+        // nor does it bind the public highest memory pointer
+        if self.permutation != PermutationMode::RunningProduct {
+            return Err("the public usage needs the product permutation".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for IoMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoMode::Lookup => write!(f, "lookup"),
+            IoMode::Evaluation => write!(f, "evaluation"),
+        }
+    }
+}
+
+impl FromStr for IoMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lookup" => Ok(IoMode::Lookup),
+            "evaluation" => Ok(IoMode::Evaluation),
+            _ => Err(format!("unknown I/O mode {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PermutationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermutationMode::RunningProduct => write!(f, "product"),
+            PermutationMode::Lookup => write!(f, "lookup"),
+        }
+    }
+}
+
+impl FromStr for PermutationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "product" => Ok(PermutationMode::RunningProduct),
+            "lookup" => Ok(PermutationMode::Lookup),
+            _ => Err(format!("unknown permutation mode {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for TapeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TapeMode::Zero => write!(f, "zero"),
+            TapeMode::Public => write!(f, "public"),
+        }
+    }
+}
+
+impl FromStr for TapeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zero" => Ok(TapeMode::Zero),
+            "public" => Ok(TapeMode::Public),
+            _ => Err(format!("unknown tape mode {}", s)),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use halo2_proofs::{
+    circuit::{AssignedCell, Region, Value},
+    plonk::{
+        Advice, Challenge, Column, ConstraintSystem, Error, Expression, SecondPhase, Selector,
+        VirtualCells,
+    },
+    poly::Rotation,
+};
+use halo2curves::ff::PrimeField;
+
+/// Running product over the rows of a table, used to prove that two tables
+/// hold the same multiset of rows:
+///
+/// z_0 = alpha - c_0, z_{i+1} = z_i * (alpha - c_{i+1}),
+///
+/// where c_i compresses the row's columns with powers of beta. Two tables are a
+/// permutation of each other (with overwhelming probability over the
+/// challenges) iff their last z values are equal.
+#[derive(Debug, Clone)]
+pub struct RunningProductConfig {
+    pub z: Column<Advice>,
+    pub s_first: Selector,
+    pub s_next: Selector,
+}
+
+impl RunningProductConfig {
+    pub fn configure<F: PrimeField>(
+        cs: &mut ConstraintSystem<F>,
+        columns: Vec<Column<Advice>>,
+        alpha: Challenge,
+        beta: Challenge,
+    ) -> Self {
+        let z = cs.advice_column_in(SecondPhase);
+        cs.enable_equality(z);
+        let s_first = cs.selector();
+        let s_next = cs.selector();
+
+        cs.create_gate("running product", |meta| {
+            let alpha = meta.query_challenge(alpha);
+            let beta = meta.query_challenge(beta);
+            let compress = |meta: &mut VirtualCells<'_, F>, rotation| {
+                columns
+                    .iter()
+                    .rev()
+                    .fold(Expression::Constant(F::from(0)), |acc, column| {
+                        acc * beta.clone() + meta.query_advice(*column, rotation)
+                    })
+            };
+            let cur_row = compress(meta, Rotation::cur());
+            let next_row = compress(meta, Rotation::next());
+            let s_first = meta.query_selector(s_first);
+            let s_next = meta.query_selector(s_next);
+            let cur_z = meta.query_advice(z, Rotation::cur());
+            let next_z = meta.query_advice(z, Rotation::next());
+
+            vec![
+                s_first * (cur_z.clone() - (alpha.clone() - cur_row)),
+                s_next * (next_z - cur_z * (alpha - next_row)),
+            ]
+        });
+
+        RunningProductConfig { z, s_first, s_next }
+    }
+
+    /// Assigns the running product next to `rows` (in the same region as the
+    /// table columns) and returns the cell holding the final product.
+    pub fn assign<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        rows: &[Vec<F>],
+        alpha: Value<F>,
+        beta: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let mut z = Value::known(F::from(1));
+        let mut last = None;
+        for (i, row) in rows.iter().enumerate() {
+            if i == 0 {
+                self.s_first.enable(region, i)?;
+            }
+            if i + 1 < rows.len() {
+                self.s_next.enable(region, i)?;
+            }
+            let compressed = beta.map(|beta| {
+                row.iter()
+                    .rev()
+                    .fold(F::from(0), |acc, value| acc * beta + value)
+            });
+            z = z * (alpha - compressed);
+            last = Some(region.assign_advice(|| "running product", self.z, i, || z)?);
+        }
+        last.ok_or(Error::Synthesis)
+    }
+}
@@ -1,60 +1,184 @@
 use crate::{
+    evaluation::RunningEvaluationConfig,
     is_zero::{IsZeroChip, IsZeroConfig},
+    params::{Arithmetic, BfCircuitParams, EofPolicy, IoMode, TapeMode},
+    permutation::RunningProductConfig,
     range_table::{RangeTable, RangeTableConfig},
     utilts::*,
 };
 use bf_vm::matrix::Matrix;
 use halo2_proofs::{
-    circuit::{Layouter, Value},
+    circuit::{AssignedCell, Layouter, Value},
     halo2curves::bn256::Fr,
     plonk::{
-        Advice, Column, ConstraintSystem, Constraints, Error, Expression, Selector, TableColumn,
+        Advice, Challenge, Column, ConstraintSystem, Constraints, Error, Expression, Selector,
+        TableColumn, VirtualCells,
     },
     poly::Rotation,
 };
+use halo2curves::ff::PrimeField;
 #[derive(Debug, Clone)]
-pub struct ProcessorTable<const RANGE: usize> {
+pub struct ProcessorTable<F: PrimeField> {
     pub clk: Column<Advice>,
     pub instruction_pointer: Column<Advice>,
     pub current_instruction: Column<Advice>,
     pub next_instruction: Column<Advice>,
+    pub operand: Column<Advice>,
     pub memory_pointer: Column<Advice>,
     pub memory_value: Column<Advice>,
-    pub mv_iszero_config: IsZeroConfig<Fr>,
+    // 1 once the input is exhausted, GETCHAR then stores the EOF value
+    pub eof: Column<Advice>,
+    // one boolean column per opcode (indexed like OPCODES), in the one-hot layout
+    pub opcode_selectors: Option<Vec<Column<Advice>>>,
+    pub mv_iszero_config: IsZeroConfig<F>,
     pub s_p: Selector, //selector for processor table
     pub s_b: Selector, //selector for boundary constraints
-    pub range_config: RangeTableConfig<RANGE>,
+    pub range_config: RangeTableConfig,
+    // permutation with the memory table, only in the running product mode
+    pub memory_product: Option<RunningProductConfig>,
+    // evaluation arguments with the output/input tables, only in STARK mode
+    pub output_evaluation: Option<RunningEvaluationConfig>,
+    pub input_evaluation: Option<RunningEvaluationConfig>,
 }
 
-fn create_deselecor(ci: Expression<Fr>, op: u8) -> Expression<Fr> {
-    let one = Expression::Constant(Fr::one());
+/// Final values of the running arguments of the processor table.
+pub struct ProcessorArguments<F: PrimeField> {
+    /// clk of the last row, the number of executed cycles
+    pub cycles: Option<AssignedCell<F, F>>,
+    pub memory_product: Option<AssignedCell<F, F>>,
+    pub output_evaluation: Option<AssignedCell<F, F>>,
+    pub input_evaluation: Option<AssignedCell<F, F>>,
+}
+
+fn create_deselecor<F: PrimeField>(ci: Expression<F>, op: u8) -> Expression<F> {
+    let one = Expression::Constant(F::from(1));
     OPCODES.iter().fold(one, |expr, v| {
         if *v == op {
             expr
         } else {
-            expr * (ci.clone() - Expression::Constant(Fr::from(*v as u64)))
+            expr * (ci.clone() - Expression::Constant(F::from(*v as u64)))
         }
     })
 }
 
-impl<const RANGE: usize> ProcessorTable<RANGE> {
-    pub fn configure(cs: &mut ConstraintSystem<Fr>) -> Self {
+// 1 if ci == op, 0 for any other opcode
+fn create_indicator<F: PrimeField>(ci: Expression<F>, op: u8) -> Expression<F> {
+    let norm = OPCODES
+        .iter()
+        .filter(|v| **v != op)
+        .fold(F::from(1), |acc, v| {
+            acc * (F::from(op as u64) - F::from(*v as u64))
+        });
+    create_deselecor(ci, op) * Expression::Constant(norm.invert().unwrap())
+}
+
+// Vanishes on every opcode but OPCODES[k]: the k-th one-hot column if there
+// are any, otherwise the degree 7 deselector.
+fn deselector<F: PrimeField>(
+    meta: &mut VirtualCells<'_, F>,
+    ci: Column<Advice>,
+    opcode_selectors: &Option<Vec<Column<Advice>>>,
+    k: usize,
+) -> Expression<F> {
+    match opcode_selectors {
+        Some(columns) => meta.query_advice(columns[k], Rotation::cur()),
+        None => create_deselecor(meta.query_advice(ci, Rotation::cur()), OPCODES[k]),
+    }
+}
+
+// 1 iff ci is OPCODES[k], 0 for any other opcode
+fn indicator<F: PrimeField>(
+    meta: &mut VirtualCells<'_, F>,
+    ci: Column<Advice>,
+    opcode_selectors: &Option<Vec<Column<Advice>>>,
+    k: usize,
+) -> Expression<F> {
+    match opcode_selectors {
+        Some(columns) => meta.query_advice(columns[k], Rotation::cur()),
+        None => create_indicator(meta.query_advice(ci, Rotation::cur()), OPCODES[k]),
+    }
+}
+
+impl<F: PrimeField> ProcessorTable<F> {
+    /// With `memory_challenges`, the processor table computes the running
+    /// product of its (clk, mp, mv) rows for the memory permutation.
+    /// With `gamma`, the processor table also computes the running evaluations
+    /// of its PUTCHAR and GETCHAR values for the output and input tables.
+    /// With `params.one_hot`, opcodes are decoded by boolean selector columns instead
+    /// of deselector polynomials, which lowers the degree of the instruction
+    /// gate from 11 to 5 at the cost of 8 advice columns.
+    /// Once the `eof` flag is set, GETCHAR stores the `params.eof` value and
+    /// takes no part in the input evaluation. Only that evaluation binds the
+    /// flag to the number of public inputs, so with the lookup I/O mode the
+    /// flag is pinned to 0 and a trace that reads past its input cannot be
+    /// proven.
+    pub fn configure(
+        cs: &mut ConstraintSystem<F>,
+        params: &BfCircuitParams,
+        memory_challenges: Option<(Challenge, Challenge)>,
+        gamma: Option<Challenge>,
+    ) -> Self {
         let clk = cs.advice_column();
+        cs.enable_equality(clk);
         let instruction_pointer = cs.advice_column();
         cs.enable_equality(instruction_pointer);
         let current_instruction = cs.advice_column();
         cs.enable_equality(current_instruction);
         let next_instruction = cs.advice_column();
         cs.enable_equality(next_instruction);
+        let operand = cs.advice_column();
+        cs.enable_equality(operand);
         let memory_pointer = cs.advice_column();
         cs.enable_equality(memory_pointer);
         let memory_value = cs.advice_column();
         cs.enable_equality(memory_value);
         let memory_value_inverse = cs.advice_column();
+        let eof = cs.advice_column();
+        let opcode_selectors = params.one_hot.then(|| {
+            OPCODES
+                .iter()
+                .map(|_| cs.advice_column())
+                .collect::<Vec<_>>()
+        });
         let s_p = cs.selector();
         let s_b = cs.selector();
+        // holds the halted state the last row is constrained to
+        let constants = cs.fixed_column();
+        cs.enable_constant(constants);
 
-        let range_config = RangeTableConfig::configure(cs);
+        let range_config = RangeTableConfig::configure(cs, params.cell_bits);
+        let memory_product = memory_challenges.map(|(alpha, beta)| {
+            RunningProductConfig::configure(
+                cs,
+                vec![clk, memory_pointer, memory_value],
+                alpha,
+                beta,
+            )
+        });
+
+        let output_evaluation = gamma.map(|gamma| {
+            RunningEvaluationConfig::configure(
+                cs,
+                "processor output evaluation",
+                gamma,
+                |meta| indicator(meta, current_instruction, &opcode_selectors, PUTCHAR),
+                |meta| meta.query_advice(memory_value, Rotation::cur()),
+            )
+        });
+        // GETCHAR writes the input to the memory value of the next row
+        let input_evaluation = gamma.map(|gamma| {
+            RunningEvaluationConfig::configure(
+                cs,
+                "processor input evaluation",
+                gamma,
+                |meta| {
+                    let eof = meta.query_advice(eof, Rotation::cur());
+                    indicator(meta, current_instruction, &opcode_selectors, GETCHAR)
+                        * (Expression::Constant(F::from(1)) - eof)
+                },
+                |meta| meta.query_advice(memory_value, Rotation::next()),
+            )
+        });
 
         let mv_iszero_config = IsZeroChip::configure(
             cs,
@@ -63,10 +187,9 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
             memory_value_inverse,
         );
 
-        let zero = Expression::Constant(Fr::zero());
-        let one = Expression::Constant(Fr::one());
-        let two = Expression::Constant(Fr::from(2));
-        let range_max = Expression::Constant(Fr::from((RANGE - 1) as u64));
+        let zero = Expression::Constant(F::from(0));
+        let one = Expression::Constant(F::from(1));
+        let range_max = Expression::Constant(F::from((1 << params.cell_bits) - 1));
 
         cs.create_gate("boundary constraints", |meta| {
             let s_b = meta.query_selector(s_b);
@@ -74,11 +197,13 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
             let ip = meta.query_advice(instruction_pointer, Rotation::cur());
             let mp = meta.query_advice(memory_pointer, Rotation::cur());
             let mv = meta.query_advice(memory_value, Rotation::cur());
-            Constraints::with_selector(
-                s_b,
-                //clk_0, ip_0, mp_0, mv_0   = 0
-                vec![cur_clk, ip, mp, mv],
-            )
+            //clk_0, ip_0, mp_0, mv_0   = 0
+            let mut constraints = vec![cur_clk, ip, mp];
+            // a public initial tape sets mv_0 through the memory table instead
+            if params.tape == TapeMode::Zero {
+                constraints.push(mv);
+            }
+            Constraints::with_selector(s_b, constraints)
         });
 
         cs.create_gate("invirant constrains", |meta| {
@@ -102,27 +227,76 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
             )
         });
 
-        cs.lookup_any("Range-Check: mv are within 0-255", |meta| {
+        cs.create_gate("eof flag", |meta| {
+            let sp = meta.query_selector(s_p);
+            let cur_eof = meta.query_advice(eof, Rotation::cur());
+            let next_eof = meta.query_advice(eof, Rotation::next());
+            let constraints = match params.io {
+                // nothing ties the flag to the public input
+                IoMode::Lookup => vec![cur_eof],
+                IoMode::Evaluation => vec![
+                    // eof is boolean
+                    cur_eof.clone() * (one.clone() - cur_eof.clone()),
+                    // and stays set once the input is exhausted
+                    cur_eof * (one.clone() - next_eof),
+                ],
+            };
+            Constraints::with_selector(sp, constraints)
+        });
+
+        if let Some(columns) = &opcode_selectors {
+            cs.create_gate("opcode selectors", |meta| {
+                let sp = meta.query_selector(s_p);
+                let ci = meta.query_advice(current_instruction, Rotation::cur());
+                let selectors = columns
+                    .iter()
+                    .map(|c| meta.query_advice(*c, Rotation::cur()))
+                    .collect::<Vec<_>>();
+                // every selector is boolean
+                let mut constraints = selectors
+                    .iter()
+                    .map(|b| b.clone() * (one.clone() - b.clone()))
+                    .collect::<Vec<_>>();
+                // exactly one of them is set
+                constraints.push(
+                    selectors
+                        .iter()
+                        .fold(zero.clone(), |acc, b| acc + b.clone())
+                        - one.clone(),
+                );
+                // and it is the one of ci
+                constraints.push(
+                    ci - selectors
+                        .iter()
+                        .zip(OPCODES)
+                        .fold(zero.clone(), |acc, (b, op)| {
+                            acc + b.clone() * Expression::Constant(F::from(op as u64))
+                        }),
+                );
+                Constraints::with_selector(sp, constraints)
+            });
+        }
+
+        cs.lookup_any("Range-Check: mv are within 0..2^cell_bits", |meta| {
             let mv = meta.query_advice(memory_value, Rotation::cur());
             let range_val = meta.query_fixed(range_config.table, Rotation::cur());
             vec![(mv, range_val)]
         });
 
         cs.create_gate("instruction constraints", |meta| {
-            let ci = meta.query_advice(current_instruction, Rotation::cur());
-            let deselectors = OPCODES
-                .iter()
-                .map(|op| create_deselecor(ci.clone(), *op))
+            let deselectors = (0..OPCODES.len())
+                .map(|k| deselector(meta, current_instruction, &opcode_selectors, k))
                 .collect::<Vec<_>>();
             let cur_mp = meta.query_advice(memory_pointer, Rotation::cur());
             let next_mp = meta.query_advice(memory_pointer, Rotation::next());
             let sp = meta.query_selector(s_p);
             let cur_ip = meta.query_advice(instruction_pointer, Rotation::cur());
-            let cur_ni = meta.query_advice(next_instruction, Rotation::cur());
+            let cur_operand = meta.query_advice(operand, Rotation::cur());
             let next_ip = meta.query_advice(instruction_pointer, Rotation::next());
             let cur_mv = meta.query_advice(memory_value, Rotation::cur());
             let next_mv = meta.query_advice(memory_value, Rotation::next());
             let mv_iszero = mv_iszero_config.expr();
+            let cur_eof = meta.query_advice(eof, Rotation::cur());
 
             //--------------------------------Instruct pointer constraints part-----------------------------//
             // ADD:+, SUB:-, SHR:>, SHL:<, GETCHAR:",", PUTCHAR:"." share the same p1 condition:
@@ -135,16 +309,15 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
                 + deselectors[PUTCHAR].clone())
                 * (next_ip.clone() - cur_ip.clone() - one.clone());
 
-            // LB:[ if mv != 0 ⇒ ip increases by 2 and if mv == 0 ⇒ ip is set to ni
+            // LB:[ if mv != 0 ⇒ ip increases by 1 and if mv == 0 ⇒ ip is set to the operand
             let expr_lb = deselectors[LB].clone()
                 * ((one.clone() - mv_iszero.clone())
-                    * (next_ip.clone() - cur_ip.clone() - two.clone())
-                    + mv_iszero.clone() * (next_ip.clone() - cur_ni.clone()));
-            // RB:] if mv == 0 ⇒ ip increases by 2 and if mv != 0 ⇒ ip is set to ni
+                    * (next_ip.clone() - cur_ip.clone() - one.clone())
+                    + mv_iszero.clone() * (next_ip.clone() - cur_operand.clone()));
+            // RB:] if mv == 0 ⇒ ip increases by 1 and if mv != 0 ⇒ ip is set to the operand
             let expr_rb = deselectors[RB].clone()
-                * (mv_iszero.clone() * (next_ip.clone() - cur_ip.clone() - two)
-                    + (one.clone() - mv_iszero.clone())
-                        * (cur_mv.clone() * (next_ip.clone() - cur_ni.clone())));
+                * (mv_iszero.clone() * (next_ip.clone() - cur_ip.clone() - one.clone())
+                    + (one.clone() - mv_iszero.clone()) * (next_ip.clone() - cur_operand));
 
             //--------------------------------Memory pointer constraints part-----------------------------//
             // ADD:+, SUB:-, LB:[, RB:], GETCHAR:, PUTCHAR share the same p2 condition:
@@ -170,18 +343,34 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
             let expr3 =
                 (deselectors[LB].clone() + deselectors[RB].clone() + deselectors[PUTCHAR].clone())
                     * (next_mv.clone() - cur_mv.clone());
-            // note: we have lookup table to ensure all mvs are within [0-255],
-            // therefore, value can only decreases by 255 iff cur_mv=255, next_mv=0
+            // note: we have lookup table to ensure all mvs are within [0, 2^cell_bits),
+            // therefore, value can only decreases by range_max iff cur_mv=range_max, next_mv=0
             // same goes for wrapping_sub
-            // ADD: mv increases by 1, or decreases by 255
+            // ADD: mv increases by 1, or decreases by range_max
+            // SUB: mv decreases by 1, or increases by range_max
+            // in strict arithmetic there is no wrapping factor, so + at range_max
+            // and - at 0 leave the range and fail the lookup
+            let (wrap_add, wrap_sub) = match params.arithmetic {
+                Arithmetic::Wrapping => (
+                    next_mv.clone() - cur_mv.clone() + range_max.clone(),
+                    next_mv.clone() - cur_mv.clone() - range_max.clone(),
+                ),
+                Arithmetic::Strict => (one.clone(), one.clone()),
+            };
             let expr_add = deselectors[ADD].clone()
                 * (next_mv.clone() - cur_mv.clone() - one.clone())
-                * (next_mv.clone() - cur_mv.clone() + range_max.clone());
-            // sub: mv decreases by 1, or increases by 255
+                * wrap_add;
             let expr_sub = deselectors[SUB].clone()
                 * (next_mv.clone() - cur_mv.clone() + one.clone())
-                * (next_mv.clone() - cur_mv.clone() - range_max.clone());
-            // SHL, SHR, GETCHAR: always true (check elsewhere)
+                * wrap_sub;
+            // GETCHAR at EOF: mv is set by the EOF policy
+            let eof_value = match params.eof {
+                EofPolicy::Zero => zero.clone(),
+                EofPolicy::Max => range_max.clone(),
+                EofPolicy::Unchanged => cur_mv.clone(),
+            };
+            let expr_eof = deselectors[GETCHAR].clone() * cur_eof * (next_mv.clone() - eof_value);
+            // SHL, SHR, GETCHAR before EOF: always true (check elsewhere)
             let expr4 = (deselectors[SHL].clone()
                 + deselectors[SHR].clone()
                 + deselectors[GETCHAR].clone())
@@ -192,7 +381,7 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
                 vec![
                     expr1 + expr_lb + expr_rb,
                     expr2 + expr_shl + expr_shr,
-                    expr3 + expr_add + expr_sub + expr4,
+                    expr3 + expr_add + expr_sub + expr_eof + expr4,
                 ],
             )
         });
@@ -202,23 +391,41 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
             instruction_pointer,
             current_instruction,
             next_instruction,
+            operand,
             memory_pointer,
             memory_value,
+            eof,
+            opcode_selectors,
             mv_iszero_config,
             s_p,
             s_b,
             range_config,
+            memory_product,
+            output_evaluation,
+            input_evaluation,
         }
     }
-    pub fn load(&mut self, mut layouter: impl Layouter<Fr>, matrix: &Matrix) -> Result<(), Error> {
+
+    /// Assigns the processor table and returns the final values of its running
+    /// arguments.
+    pub fn load(
+        &mut self,
+        mut layouter: impl Layouter<F>,
+        matrix: &Matrix,
+        alpha: Value<F>,
+        beta: Value<F>,
+        gamma: Value<F>,
+    ) -> Result<ProcessorArguments<F>, Error> {
         let processor_mat = &matrix.processor_matrix;
         let iszero_chip = IsZeroChip::construct(self.mv_iszero_config.clone());
 
         self.range_config.load_table(&mut layouter)?;
 
-        layouter.assign_region(
+        let arguments = layouter.assign_region(
             || "processor table",
             |mut region| {
+                let mut cycles = None;
+                let mut halt = None;
                 for i in 0..processor_mat.len() {
                     if i == 0 {
                         self.s_b.enable(&mut region, i)?;
@@ -226,59 +433,145 @@ impl<const RANGE: usize> ProcessorTable<RANGE> {
                         self.s_p.enable(&mut region, i - 1)?;
                     }
 
-                    region.assign_advice(
+                    let clk = region.assign_advice(
                         || "clk cell",
                         self.clk,
                         i,
-                        || Value::known(processor_mat[i].cycle),
+                        || Value::known(to_field(&processor_mat[i].cycle)),
                     )?;
+                    cycles = Some(clk);
 
-                    region.assign_advice(
+                    let ip = region.assign_advice(
                         || "instruction pointer cell",
                         self.instruction_pointer,
                         i,
-                        || Value::known(processor_mat[i].instruction_pointer),
+                        || Value::known(to_field(&processor_mat[i].instruction_pointer)),
                     )?;
 
-                    region.assign_advice(
+                    let ci = region.assign_advice(
                         || "current_instruction cell",
                         self.current_instruction,
                         i,
-                        || Value::known(processor_mat[i].current_instruction),
+                        || Value::known(to_field(&processor_mat[i].current_instruction)),
                     )?;
+                    halt = Some((ip, ci));
 
                     region.assign_advice(
                         || "next_instruction cell",
                         self.next_instruction,
                         i,
-                        || Value::known(processor_mat[i].next_instruction),
+                        || Value::known(to_field(&processor_mat[i].next_instruction)),
+                    )?;
+
+                    region.assign_advice(
+                        || "operand cell",
+                        self.operand,
+                        i,
+                        || Value::known(to_field(&processor_mat[i].operand)),
                     )?;
 
                     region.assign_advice(
                         || "memory_pointer cell",
                         self.memory_pointer,
                         i,
-                        || Value::known(processor_mat[i].memory_pointer),
+                        || Value::known(to_field(&processor_mat[i].memory_pointer)),
                     )?;
 
                     region.assign_advice(
                         || "memory_value cell",
                         self.memory_value,
                         i,
-                        || Value::known(processor_mat[i].memory_value),
+                        || Value::known(to_field(&processor_mat[i].memory_value)),
                     )?;
 
+                    region.assign_advice(
+                        || "eof cell",
+                        self.eof,
+                        i,
+                        || Value::known(to_field(&processor_mat[i].eof)),
+                    )?;
+
+                    if let Some(columns) = &self.opcode_selectors {
+                        for (column, op) in columns.iter().zip(OPCODES) {
+                            let selected =
+                                processor_mat[i].current_instruction == Fr::from(op as u64);
+                            region.assign_advice(
+                                || "opcode selector cell",
+                                *column,
+                                i,
+                                || Value::known(F::from(selected as u64)),
+                            )?;
+                        }
+                    }
+
                     iszero_chip.assign(
                         &mut region,
                         i,
-                        Value::known(processor_mat[i].memory_value),
+                        Value::known(to_field(&processor_mat[i].memory_value)),
                     )?;
                 }
 
-                Ok(())
+                // the last row is the halted state, so `cycles` counts the
+                // whole execution: ip past the end of the program, ci 0
+                if let Some((ip, ci)) = halt {
+                    region.constrain_constant(ip.cell(), F::from(matrix.program.len() as u64))?;
+                    region.constrain_constant(ci.cell(), F::from(0))?;
+                }
+
+                let rows = processor_mat
+                    .iter()
+                    .map(|r| {
+                        [r.cycle, r.memory_pointer, r.memory_value]
+                            .iter()
+                            .map(to_field::<F>)
+                            .collect()
+                    })
+                    .collect::<Vec<_>>();
+                let memory_product = match &self.memory_product {
+                    Some(config) => Some(config.assign(&mut region, &rows, alpha, beta)?),
+                    None => None,
+                };
+
+                // one term per transition, the last row has no successor;
+                // GETCHAR at EOF reads nothing
+                let terms = |op: usize, next: bool| {
+                    processor_mat
+                        .windows(2)
+                        .map(|w| {
+                            let ind = if w[0].current_instruction == Fr::from(OPCODES[op] as u64)
+                                && (op != GETCHAR || w[0].eof == Fr::from(0))
+                            {
+                                F::from(1)
+                            } else {
+                                F::from(0)
+                            };
+                            let value = if next { &w[1] } else { &w[0] }.memory_value;
+                            (ind, to_field(&value))
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let output_evaluation = match &self.output_evaluation {
+                    Some(config) => {
+                        Some(config.assign(&mut region, &terms(PUTCHAR, false), gamma)?)
+                    }
+                    None => None,
+                };
+                let input_evaluation = match &self.input_evaluation {
+                    Some(config) => {
+                        Some(config.assign(&mut region, &terms(GETCHAR, true), gamma)?)
+                    }
+                    None => None,
+                };
+
+                Ok(ProcessorArguments {
+                    cycles,
+                    memory_product,
+                    output_evaluation,
+                    input_evaluation,
+                })
             },
         )?;
 
-        Ok(())
+        Ok(arguments)
     }
 }
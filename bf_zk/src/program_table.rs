@@ -1,28 +1,36 @@
+use crate::utilts::to_field;
 use bf_vm::matrix::Matrix;
 use halo2_proofs::{
     circuit::{layouter, Layouter, Value},
-    halo2curves::bn256::Fr,
     plonk::{Advice, Column, ConstraintSystem, Error, Fixed, TableColumn},
 };
+use halo2curves::ff::PrimeField;
 #[derive(Debug, Clone)]
 pub struct ProgramTable {
     pub instruction_pointer: Column<Fixed>,
     pub current_instruction: Column<Fixed>,
     pub next_instruction: Column<Fixed>,
+    pub operand: Column<Fixed>,
 }
 
 impl ProgramTable {
-    pub fn configure(cs: &mut ConstraintSystem<Fr>) -> Self {
+    pub fn configure<F: PrimeField>(cs: &mut ConstraintSystem<F>) -> Self {
         let instruction_pointer = cs.fixed_column();
         let current_instruction = cs.fixed_column();
         let next_instruction = cs.fixed_column();
+        let operand = cs.fixed_column();
         ProgramTable {
             instruction_pointer,
             current_instruction,
             next_instruction,
+            operand,
         }
     }
-    pub fn load(&mut self, mut layouter: impl Layouter<Fr>, matrix: &Matrix) -> Result<(), Error> {
+    pub fn load<F: PrimeField>(
+        &mut self,
+        mut layouter: impl Layouter<F>,
+        matrix: &Matrix,
+    ) -> Result<(), Error> {
         layouter.assign_region(
             || "program table",
             |mut region| {
@@ -32,22 +40,46 @@ impl ProgramTable {
                         || "instruction_pointer cell",
                         self.instruction_pointer,
                         i,
-                        || Value::known(program[i].instruction_pointer),
+                        || Value::known(to_field(&program[i].instruction_pointer)),
                     )?;
 
                     region.assign_fixed(
                         || "current_instruction cell",
                         self.current_instruction,
                         i,
-                        || Value::known(program[i].current_instruction),
+                        || Value::known(to_field(&program[i].current_instruction)),
                     )?;
 
                     region.assign_fixed(
                         || "next_instruction cell",
                         self.next_instruction,
                         i,
-                        || Value::known(program[i].next_instruction),
+                        || Value::known(to_field(&program[i].next_instruction)),
                     )?;
+
+                    region.assign_fixed(
+                        || "operand cell",
+                        self.operand,
+                        i,
+                        || Value::known(to_field(&program[i].operand)),
+                    )?;
+                }
+
+                // terminal row (len, 0, 0, 0): where the instruction pointer
+                // stops once the program halts
+                let len = program.len();
+                region.assign_fixed(
+                    || "instruction_pointer cell",
+                    self.instruction_pointer,
+                    len,
+                    || Value::known(F::from(len as u64)),
+                )?;
+                for (column, name) in [
+                    (self.current_instruction, "current_instruction cell"),
+                    (self.next_instruction, "next_instruction cell"),
+                    (self.operand, "operand cell"),
+                ] {
+                    region.assign_fixed(|| name, column, len, || Value::known(F::from(0)))?;
                 }
                 Ok(())
             },
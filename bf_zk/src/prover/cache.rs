@@ -0,0 +1,205 @@
+//! On-disk cache of KZG parameters and keys. The program is baked into fixed
+//! columns and the selectors are enabled on the rows of the trace, so the keys
+//! depend on both: they are stored under a hash of (circuit version, program,
+//! table lengths, k, circuit params, setup), and any change to one of them
+//! makes the cache generate fresh keys.
+//!
+//! This is not a per-program key: an input that changes the number of
+//! cycles, I/O values or touched cells gets keys of its own. Keying by
+//! (program, k, params) alone needs a layout whose selectors do not follow
+//! the trace, e.g. enabled over `max_cycles` rows with padding that
+//! satisfies the gates, which the circuit does not have yet.
+use crate::main_config::{VMCircuit, CIRCUIT_VERSION};
+use crate::params::BfCircuitParams;
+use bf_vm::matrix::{InstructionMatrixRow, Matrix};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{keygen_pk, keygen_vk, Error, ProvingKey, VerifyingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    SerdeFormat,
+};
+use halo2curves::ff::PrimeField;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io(io::Error),
+    Keygen(Error),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Io(e) => write!(f, "key cache: {}", e),
+            CacheError::Keygen(e) => write!(f, "keygen failed: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<io::Error> for CacheError {
+    fn from(e: io::Error) -> Self {
+        CacheError::Io(e)
+    }
+}
+
+impl From<Error> for CacheError {
+    fn from(e: Error) -> Self {
+        CacheError::Keygen(e)
+    }
+}
+
+/// Hash of the program rows the circuit puts in its fixed columns.
+pub fn program_hash(program: &[InstructionMatrixRow]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for row in program {
+        for v in [
+            row.instruction_pointer,
+            row.current_instruction,
+            row.next_instruction,
+            row.operand,
+        ] {
+            hasher.update(v.to_repr());
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Lengths of the tables of `matrix`. Keygen enables the selectors and lays
+/// out the copy constraints over exactly these rows, so two traces of one
+/// program share keys only if they agree on all of them.
+pub fn table_lengths(matrix: &Matrix) -> [usize; 6] {
+    [
+        matrix.processor_matrix.len(),
+        matrix.instruction_matrix.len(),
+        matrix.memory_matrix.len(),
+        matrix.input_matrix.len(),
+        matrix.output_matrix.len(),
+        // rows of the public tapes in `TapeMode::Public`
+        matrix.initial_tape().len(),
+    ]
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub struct KeyCache {
+    dir: PathBuf,
+}
+
+impl KeyCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        KeyCache { dir: dir.into() }
+    }
+
+    /// Loads the setup for `k`, or generates and stores one. Keys are only
+    /// valid for the setup they were generated with, so proofs that should
+    /// share keys have to share this file too.
+    pub fn params(&self, k: u32) -> Result<ParamsKZG<Bn256>, CacheError> {
+        let path = self.dir.join(format!("params-{}.bin", k));
+        if path.exists() {
+            return Ok(ParamsKZG::read(&mut BufReader::new(File::open(path)?))?);
+        }
+        let params = super::kzg::setup(k);
+        fs::create_dir_all(&self.dir)?;
+        write_atomic(&path, |w| params.write(w))?;
+        Ok(params)
+    }
+
+    // the key files are this path with a pk or vk extension
+    fn key_path(&self, params: &ParamsKZG<Bn256>, circuit: &VMCircuit<Fr>) -> PathBuf {
+        let BfCircuitParams {
+            cell_bits,
+            // only checked by the prover, the keys do not depend on it
+            max_cycles: _,
+            io,
+            permutation,
+            one_hot,
+            eof,
+            arithmetic,
+            tape,
+        } = circuit.params;
+        let mut setup = vec![];
+        params
+            .write(&mut setup)
+            .expect("writing to a Vec cannot fail");
+
+        let mut hasher = Sha256::new();
+        hasher.update(CIRCUIT_VERSION.to_le_bytes());
+        hasher.update(program_hash(&circuit.matrix.program));
+        for len in table_lengths(&circuit.matrix) {
+            hasher.update((len as u64).to_le_bytes());
+        }
+        hasher.update(params.k().to_le_bytes());
+        hasher.update((cell_bits as u64).to_le_bytes());
+        hasher.update(format!(
+            "{}/{}/{}/{}/{}/{}",
+            io, permutation, one_hot, eof, arithmetic, tape
+        ));
+        hasher.update(Sha256::digest(&setup));
+        self.dir.join(hex(&hasher.finalize()))
+    }
+
+    /// Loads the proving key of `circuit`'s program, table lengths and params,
+    /// or generates and stores it together with its verifying key.
+    pub fn proving_key(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        circuit: &VMCircuit<Fr>,
+    ) -> Result<ProvingKey<G1Affine>, CacheError> {
+        let path = self.key_path(params, circuit);
+        let (pk_path, vk_path) = (path.with_extension("pk"), path.with_extension("vk"));
+        if pk_path.exists() {
+            return Ok(ProvingKey::read::<_, VMCircuit<Fr>>(
+                &mut BufReader::new(File::open(pk_path)?),
+                SerdeFormat::RawBytes,
+                circuit.params,
+            )?);
+        }
+        let vk = keygen_vk(params, circuit)?;
+        let pk = keygen_pk(params, vk, circuit)?;
+        fs::create_dir_all(&self.dir)?;
+        write_atomic(&pk_path, |w| pk.write(w, SerdeFormat::RawBytes))?;
+        write_atomic(&vk_path, |w| pk.get_vk().write(w, SerdeFormat::RawBytes))?;
+        Ok(pk)
+    }
+
+    /// Loads the verifying key of `circuit`'s program, table lengths and
+    /// params, generating it if neither it nor the proving key were cached.
+    pub fn verifying_key(
+        &self,
+        params: &ParamsKZG<Bn256>,
+        circuit: &VMCircuit<Fr>,
+    ) -> Result<VerifyingKey<G1Affine>, CacheError> {
+        let path = self.key_path(params, circuit).with_extension("vk");
+        if path.exists() {
+            return Ok(VerifyingKey::read::<_, VMCircuit<Fr>>(
+                &mut BufReader::new(File::open(path)?),
+                SerdeFormat::RawBytes,
+                circuit.params,
+            )?);
+        }
+        Ok(self.proving_key(params, circuit)?.get_vk().clone())
+    }
+}
+
+// a crash while writing must not leave a truncated key behind
+fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    write(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    fs::rename(tmp, path)
+}
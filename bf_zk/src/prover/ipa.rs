@@ -0,0 +1,73 @@
+//! IPA over the Pasta curves. Needs no trusted setup, at the cost of larger
+//! proofs and a verifier linear in the circuit size.
+use halo2_proofs::{
+    halo2curves::pasta::{EqAffine, Fp},
+    plonk::{
+        create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, Error, ProvingKey, VerifyingKey,
+    },
+    poly::{
+        commitment::ParamsProver,
+        ipa::{
+            commitment::{IPACommitmentScheme, ParamsIPA},
+            multiopen::{ProverIPA, VerifierIPA},
+            strategy::SingleStrategy,
+        },
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use rand_core::OsRng;
+
+/// Transparent setup, the parameters only depend on `k`.
+pub fn setup(k: u32) -> ParamsIPA<EqAffine> {
+    ParamsIPA::new(k)
+}
+
+/// Generates the keys. The selectors are enabled on the rows of `circuit`'s
+/// trace, so the keys only prove traces with the same table lengths.
+pub fn keygen<C: Circuit<Fp>>(
+    params: &ParamsIPA<EqAffine>,
+    circuit: &C,
+) -> Result<ProvingKey<EqAffine>, Error> {
+    let vk = keygen_vk(params, circuit)?;
+    keygen_pk(params, vk, circuit)
+}
+
+pub fn prove<C: Circuit<Fp>>(
+    params: &ParamsIPA<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+    circuit: C,
+    instances: &[Vec<Fp>],
+) -> Result<Vec<u8>, Error> {
+    let instances = instances.iter().map(|i| i.as_slice()).collect::<Vec<_>>();
+    let mut transcript = Blake2bWrite::<_, EqAffine, Challenge255<_>>::init(vec![]);
+    create_proof::<IPACommitmentScheme<EqAffine>, ProverIPA<_>, _, _, _, _>(
+        params,
+        pk,
+        &[circuit],
+        &[&instances],
+        OsRng,
+        &mut transcript,
+    )?;
+    Ok(transcript.finalize())
+}
+
+pub fn verify(
+    params: &ParamsIPA<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &[u8],
+    instances: &[Vec<Fp>],
+) -> Result<(), Error> {
+    let instances = instances.iter().map(|i| i.as_slice()).collect::<Vec<_>>();
+    let params = params.verifier_params();
+    let mut transcript = Blake2bRead::<_, EqAffine, Challenge255<_>>::init(proof);
+    verify_proof::<IPACommitmentScheme<EqAffine>, VerifierIPA<_>, _, _, _>(
+        params,
+        vk,
+        SingleStrategy::new(params),
+        &[&instances],
+        &mut transcript,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Real proving and verification of the VM circuit, as opposed to the
+//! `MockProver` used by the tests, with either KZG over BN254 or IPA over the
+//! Pasta curves.
+use crate::utilts::to_field;
+use bf_vm::interpreter::FieldExt;
+use bf_vm::matrix::Matrix;
+use halo2_proofs::halo2curves::bn256::Fr;
+use halo2curves::ff::PrimeField;
+
+pub mod cache;
+pub mod ipa;
+pub mod kzg;
+
+/// Resources used by an execution, public in the circuit so that a verifier
+/// can hold a proof to a budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub cycles: u64,
+    /// Highest memory pointer, the program touched `max_memory_pointer + 1`
+    /// cells. Only bound with `PermutationMode::RunningProduct`, the memory
+    /// lookup would let a prover drop the rows of the higher cells.
+    pub max_memory_pointer: u64,
+}
+
+impl Usage {
+    pub fn of(matrix: &Matrix) -> Self {
+        let value = |v: Option<&Fr>| v.map_or(0, |v| v.get_lower_128() as u64);
+        Usage {
+            cycles: value(matrix.processor_matrix.last().map(|r| &r.cycle)),
+            max_memory_pointer: value(matrix.memory_matrix.last().map(|r| &r.memory_pointer)),
+        }
+    }
+
+    /// The usage instance column.
+    pub fn instance<F: PrimeField>(&self) -> Vec<F> {
+        vec![F::from(self.cycles), F::from(self.max_memory_pointer)]
+    }
+}
+
+/// Public inputs of the circuit: the output values, the input values and the
+/// [`Usage`]. `TapeMode::Public` circuits also take [`tape_instances`].
+pub fn instances<F: PrimeField>(matrix: &Matrix) -> Vec<Vec<F>> {
+    vec![
+        matrix
+            .output_matrix
+            .iter()
+            .map(|r| to_field(&r.value))
+            .collect(),
+        matrix
+            .input_matrix
+            .iter()
+            .map(|r| to_field(&r.value))
+            .collect(),
+        Usage::of(matrix).instance(),
+    ]
+}
+
+/// The initial and the final tape of the touched cells, the instance columns
+/// after [`instances`] in `TapeMode::Public`. They hold one raw value per
+/// cell, so they are public in full.
+pub fn tape_instances<F: PrimeField>(matrix: &Matrix) -> Vec<Vec<F>> {
+    vec![
+        matrix.initial_tape().iter().map(to_field).collect(),
+        matrix.final_tape().iter().map(to_field).collect(),
+    ]
+}
@@ -1,34 +1,35 @@
 use halo2_proofs::circuit::{Layouter, Value};
-use halo2_proofs::halo2curves::bn256::Fr;
 use halo2_proofs::plonk::*;
+use halo2curves::ff::PrimeField;
 
-pub trait RangeTable {
-    fn configure(cs: &mut ConstraintSystem<Fr>) -> Self;
-    fn load_table(&self, layouter: &mut impl Layouter<Fr>) -> Result<(), Error>;
+pub trait RangeTable<F: PrimeField> {
+    fn configure(cs: &mut ConstraintSystem<F>, bits: usize) -> Self;
+    fn load_table(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error>;
 }
 
 #[derive(Clone, Debug, Copy)]
-pub struct RangeTableConfig<const RANGE: usize> {
+pub struct RangeTableConfig {
     pub table: Column<Fixed>,
+    pub bits: usize,
 }
 
-impl<const RANGE: usize> RangeTable for RangeTableConfig<RANGE> {
-    fn configure(cs: &mut ConstraintSystem<Fr>) -> Self {
+impl<F: PrimeField> RangeTable<F> for RangeTableConfig {
+    fn configure(cs: &mut ConstraintSystem<F>, bits: usize) -> Self {
         let table = cs.fixed_column();
-        Self { table }
+        Self { table, bits }
     }
 
-    fn load_table(&self, layouter: &mut impl Layouter<Fr>) -> Result<(), Error> {
+    fn load_table(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
         layouter.assign_region(
             || "load range-check table",
             |mut region| {
                 let mut offset = 0;
-                for value in 0 as i64..1 << RANGE {
+                for value in 0 as i64..1 << self.bits {
                     region.assign_fixed(
                         || "value",
                         self.table,
                         offset,
-                        || Value::known(Fr::from(value as u64)),
+                        || Value::known(F::from(value as u64)),
                     )?;
                     offset += 1;
                 }
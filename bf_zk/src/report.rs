@@ -0,0 +1,153 @@
+//! Size and cost of the VM circuit for a given trace and `k`, without proving.
+use crate::main_config::VMCircuit;
+use crate::params::IoMode;
+use halo2_proofs::{
+    dev::cost::CircuitCost,
+    halo2curves::bn256::{Fr, G1},
+    plonk::{Circuit, ConstraintSystem},
+};
+use std::fmt;
+
+/// Rows used by each table of the circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableRows {
+    pub program: usize,
+    pub instruction: usize,
+    pub processor: usize,
+    pub memory: usize,
+    /// Output and input tables, or their running evaluations in
+    /// `IoMode::Evaluation`.
+    pub output: usize,
+    pub input: usize,
+    pub range: usize,
+}
+
+impl TableRows {
+    /// The largest table, which decides the `k` the circuit needs.
+    pub fn max(&self) -> usize {
+        [
+            self.program,
+            self.instruction,
+            self.processor,
+            self.memory,
+            self.output,
+            self.input,
+            self.range,
+        ]
+        .into_iter()
+        .max()
+        .unwrap()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitReport {
+    pub k: u32,
+    pub rows: TableRows,
+    /// Rows of the `2^k` that are not reserved for blinding.
+    pub usable_rows: usize,
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub instance_columns: usize,
+    pub selectors: usize,
+    pub degree: usize,
+    pub lookups: usize,
+    /// halo2's `CircuitCost` estimate of the proof size, in bytes.
+    pub proof_size: usize,
+}
+
+impl CircuitReport {
+    pub fn fits(&self) -> bool {
+        self.rows.max() <= self.usable_rows
+    }
+}
+
+/// Measures `circuit` at size `k`. Its witness only provides the table sizes.
+pub fn report(k: u32, circuit: &VMCircuit<Fr>) -> CircuitReport {
+    let mut cs = ConstraintSystem::<Fr>::default();
+    VMCircuit::<Fr>::configure_with_params(&mut cs, circuit.params());
+    let cost = CircuitCost::<G1, _>::measure(k, circuit);
+
+    let matrix = &circuit.matrix;
+    // the running evaluation has one row more than values
+    let io_rows = |values: usize| match circuit.params.io {
+        IoMode::Lookup => values,
+        IoMode::Evaluation => values + 1,
+    };
+    let rows = TableRows {
+        program: matrix.program.len(),
+        instruction: matrix.instruction_matrix.len(),
+        processor: matrix.processor_matrix.len(),
+        memory: matrix.memory_matrix.len(),
+        output: io_rows(matrix.output_matrix.len()),
+        input: io_rows(matrix.input_matrix.len()),
+        range: 1 << circuit.params.cell_bits,
+    };
+
+    CircuitReport {
+        k,
+        rows,
+        usable_rows: (1 << k) - (cs.blinding_factors() + 1),
+        advice_columns: cs.num_advice_columns(),
+        fixed_columns: cs.num_fixed_columns(),
+        instance_columns: cs.num_instance_columns(),
+        selectors: cs.num_selectors(),
+        degree: cs.degree(),
+        lookups: cs.lookups().len(),
+        proof_size: cost.proof_size(1).into(),
+    }
+}
+
+impl fmt::Display for CircuitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "k = {}, {} of {} usable rows{}",
+            self.k,
+            self.rows.max(),
+            self.usable_rows,
+            if self.fits() { "" } else { " (does not fit)" }
+        )?;
+        let rows = &self.rows;
+        writeln!(
+            f,
+            "rows: program {}, instruction {}, processor {}, memory {}, output {}, input {}, range {}",
+            rows.program,
+            rows.instruction,
+            rows.processor,
+            rows.memory,
+            rows.output,
+            rows.input,
+            rows.range
+        )?;
+        writeln!(
+            f,
+            "columns: {} advice, {} fixed, {} instance, {} selectors",
+            self.advice_columns, self.fixed_columns, self.instance_columns, self.selectors
+        )?;
+        write!(
+            f,
+            "degree {}, {} lookups, ~{} bytes proof",
+            self.degree, self.lookups, self.proof_size
+        )
+    }
+}
+
+/// Renders the layout of `circuit` at size `k` to a PNG at `path`.
+#[cfg(feature = "dev-graph")]
+pub fn render_layout(
+    k: u32,
+    circuit: &VMCircuit<Fr>,
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use plotters::prelude::*;
+
+    let root = BitMapBackend::new(path, (1024, 768)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let root = root.titled("VM circuit", ("sans-serif", 60))?;
+    halo2_proofs::dev::CircuitLayout::default()
+        .show_labels(true)
+        .render(k, circuit, &root)?;
+    root.present()?;
+    Ok(())
+}
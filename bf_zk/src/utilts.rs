@@ -1,4 +1,7 @@
 use bf_vm::code;
+use bf_vm::interpreter::FieldExt;
+use halo2_proofs::halo2curves::bn256::Fr;
+use halo2curves::ff::PrimeField;
 
 pub const OPCODES: [u8; 8] = [
     code::SHL,
@@ -19,3 +22,10 @@ pub const GETCHAR: usize = 4;
 pub const PUTCHAR: usize = 5;
 pub const LB: usize = 6;
 pub const RB: usize = 7;
+
+/// The VM computes over BN254's scalar field; its trace values are small
+/// integers that embed into any circuit field.
+pub fn to_field<F: PrimeField>(v: &Fr) -> F {
+    let v = v.get_lower_128();
+    F::from((v >> 64) as u64) * F::from(1 << 32).square() + F::from(v as u64)
+}
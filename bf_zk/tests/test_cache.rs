@@ -0,0 +1,82 @@
+use bf_vm::{code, interpreter::Interpreter, matrix::Matrix};
+use bf_zk::main_config::VMCircuit;
+use bf_zk::params::BfCircuitParams;
+use bf_zk::prover::{self, cache::KeyCache};
+use halo2_proofs::halo2curves::bn256::Fr;
+
+fn trace(source: &[u8], input: &str) -> Matrix {
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(source.to_vec()));
+    vm.set_input(code::easygen(input));
+    vm.run();
+    vm.matrix
+}
+
+fn key_files(dir: &std::path::Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().unwrap() == "pk")
+        .count()
+}
+
+#[test]
+fn test_key_cache() {
+    let dir = std::env::temp_dir().join(format!("bf_zk_key_cache_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = KeyCache::new(&dir);
+    let source = include_bytes!("../../res/neptune_tutorial.bf");
+    let params = cache.params(9).unwrap();
+
+    let circuit = VMCircuit::<Fr>::new(trace(source, "a"), BfCircuitParams::default());
+    cache.proving_key(&params, &circuit).unwrap();
+    assert_eq!(key_files(&dir), 1);
+
+    // another input of the same program and trace lengths reuses the stored
+    // keys
+    let matrix = trace(source, "b");
+    let instances = prover::instances(&matrix);
+    let circuit = VMCircuit::<Fr>::new(matrix, BfCircuitParams::default());
+    let params = cache.params(9).unwrap();
+    let pk = cache.proving_key(&params, &circuit).unwrap();
+    let vk = cache.verifying_key(&params, &circuit).unwrap();
+    assert_eq!(key_files(&dir), 1);
+    let proof = prover::kzg::prove(&params, &pk, circuit, &instances).unwrap();
+    prover::kzg::verify(&params, &vk, &proof, &instances).unwrap();
+
+    // a different program or different circuit params need new keys
+    let circuit = VMCircuit::<Fr>::new(trace(b"+.", ""), BfCircuitParams::default());
+    cache.proving_key(&params, &circuit).unwrap();
+    assert_eq!(key_files(&dir), 2);
+    let one_hot = BfCircuitParams {
+        one_hot: true,
+        ..Default::default()
+    };
+    let circuit = VMCircuit::<Fr>::new(trace(b"+.", ""), one_hot);
+    cache.proving_key(&params, &circuit).unwrap();
+    assert_eq!(key_files(&dir), 3);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_key_cache_trace_length() {
+    let dir = std::env::temp_dir().join(format!("bf_zk_key_cache_len_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = KeyCache::new(&dir);
+    // counts the input down, so "c" runs for more cycles than "a"
+    let source = b",[.-]";
+    let params = cache.params(10).unwrap();
+
+    for (input, keys) in [("a", 1), ("c", 2), ("a", 2)] {
+        let matrix = trace(source, input);
+        let instances = prover::instances(&matrix);
+        let circuit = VMCircuit::<Fr>::new(matrix, BfCircuitParams::default());
+        let pk = cache.proving_key(&params, &circuit).unwrap();
+        let vk = cache.verifying_key(&params, &circuit).unwrap();
+        assert_eq!(key_files(&dir), keys);
+        let proof = prover::kzg::prove(&params, &pk, circuit, &instances).unwrap();
+        prover::kzg::verify(&params, &vk, &proof, &instances).unwrap();
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,34 @@
+use bf_vm::{code, interpreter::Interpreter};
+use bf_zk::explain::explain_failures;
+use bf_zk::main_config::VMCircuit;
+use bf_zk::params::BfCircuitParams;
+use bf_zk::prover;
+use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr};
+
+#[test]
+fn test_explain_corrupted_ip() {
+    let program = code::compile(include_bytes!("../../res/hello_world.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.run();
+    // cycle 4 is a `+`, so ip must step from 4 to 5
+    vm.matrix.processor_matrix[5].instruction_pointer += Fr::one();
+
+    let instances = prover::instances(&vm.matrix);
+    let matrix = vm.matrix.clone();
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+    let prover = MockProver::run(10, &vmcircuit, instances).unwrap();
+    let failures = prover.verify().unwrap_err();
+    let explanations = explain_failures(&failures, &matrix);
+    for e in &explanations {
+        println!("{}", e);
+    }
+    let e = explanations
+        .iter()
+        .find(|e| e.cycle == Some(4) && e.subexpression == Some("ip"))
+        .unwrap();
+    assert_eq!(e.instruction.as_deref(), Some("+"));
+    assert!(e
+        .to_string()
+        .starts_with("cycle 4, ip 4, instruction '+', mp 0, mv 4"));
+}
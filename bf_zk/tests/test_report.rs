@@ -0,0 +1,58 @@
+use bf_vm::{code, interpreter::Interpreter, matrix::Matrix};
+use bf_zk::main_config::VMCircuit;
+use bf_zk::params::{BfCircuitParams, IoMode};
+use bf_zk::report::report;
+
+fn neptune() -> Matrix {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    vm.matrix
+}
+
+#[test]
+fn test_report_rows() {
+    let matrix = neptune();
+    let circuit = VMCircuit::new(matrix.clone(), BfCircuitParams::default());
+
+    let r = report(9, &circuit);
+    assert_eq!(r.rows.program, matrix.program.len());
+    assert_eq!(r.rows.instruction, matrix.instruction_matrix.len());
+    assert_eq!(r.rows.processor, matrix.processor_matrix.len());
+    assert_eq!(r.rows.memory, matrix.memory_matrix.len());
+    assert_eq!(r.rows.output, matrix.output_matrix.len());
+    assert_eq!(r.rows.range, 256);
+    assert_eq!(r.instance_columns, 3);
+    assert!(r.fits());
+    assert!(r.proof_size > 0);
+
+    // the range table alone takes all 256 rows
+    assert!(!report(8, &circuit).fits());
+}
+
+#[test]
+fn test_report_modes() {
+    let matrix = neptune();
+    let base = report(
+        9,
+        &VMCircuit::new(matrix.clone(), BfCircuitParams::default()),
+    );
+
+    let one_hot = BfCircuitParams {
+        one_hot: true,
+        ..Default::default()
+    };
+    let one_hot = report(9, &VMCircuit::new(matrix.clone(), one_hot));
+    assert_eq!(one_hot.advice_columns, base.advice_columns + 8);
+    assert!(one_hot.degree < base.degree);
+
+    let evaluation = BfCircuitParams {
+        io: IoMode::Evaluation,
+        ..Default::default()
+    };
+    let evaluation = report(9, &VMCircuit::new(matrix.clone(), evaluation));
+    assert_eq!(evaluation.rows.output, matrix.output_matrix.len() + 1);
+    assert!(evaluation.lookups < base.lookups);
+}
@@ -1,8 +1,17 @@
-use std::{marker::PhantomData, usize};
+use std::usize;
 
-use bf_vm::{code, interpreter::Interpreter};
+use bf_vm::{
+    code,
+    interpreter::{program_rows, Arithmetic, EofPolicy, Interpreter, Register},
+    matrix::{InstructionMatrixRow, Matrix, MemoryMatrixRow},
+};
 use bf_zk::main_config::VMCircuit;
-use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr};
+use bf_zk::params::{BfCircuitParams, IoMode, PermutationMode, TapeMode};
+use bf_zk::prover;
+use halo2_proofs::{
+    dev::MockProver,
+    halo2curves::{bn256::Fr, pasta::Fp},
+};
 
 #[test]
 fn test_run() {
@@ -26,38 +35,23 @@ fn test_run() {
     );
 }
 
-fn mock_prove_circuit(program: Vec<Fr>, input: Vec<Fr>, k: u32) {
+fn mock_prove_circuit(program: Vec<code::Instruction>, input: Vec<Fr>, k: u32) {
     let mut vm = Interpreter::new();
     vm.set_code(program);
     vm.set_input(input);
     vm.run();
 
-    let input_val = vm
-        .matrix
-        .input_matrix
-        .iter()
-        .map(|v| v.value)
-        .collect::<Vec<Fr>>();
-    let output_val = vm
-        .matrix
-        .output_matrix
-        .iter()
-        .map(|v| v.value)
-        .collect::<Vec<Fr>>();
-
-    let vmcircuit = VMCircuit::<Fr, 8> {
-        matrix: vm.matrix,
-        _marker: PhantomData,
-    };
+    let instances = prover::instances(&vm.matrix);
 
-    let prover =
-        MockProver::run(k, &vmcircuit, vec![output_val.clone(), input_val.clone()]).unwrap();
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+
+    let prover = MockProver::run(k, &vmcircuit, instances).unwrap();
     prover.assert_satisfied();
 }
 
 #[test]
 fn test_vmcircuit() {
-    let k = 9;
+    let k = 10;
     let program = code::compile(include_bytes!("../../res/hello_world.bf").to_vec());
     let input = vec![];
     mock_prove_circuit(program, input, k);
@@ -71,25 +65,540 @@ fn test_vmcircuit_2() {
     mock_prove_circuit(program, input, k);
 }
 
-#[cfg(feature = "dev-graph")]
-#[test]
-fn vmcircuit_graph() {
-    let circuit = setup_circuit();
-    use plotters::prelude::*;
-    let root = BitMapBackend::new("./simple.png", (1024, 768)).into_drawing_area();
-    root.fill(&WHITE).unwrap();
-    let root = root
-        .titled("Simple Circuit without chip", ("sans-serif", 60))
-        .unwrap();
-
-    halo2_proofs::dev::CircuitLayout::default()
-        // You can optionally render only a section of the circuit.
-        // .view_width(0..2)
-        // .view_height(0..16)
-        // You can hide labels, which can be useful with smaller areas.
-        .show_labels(true)
-        // Render the circuit onto your area!
-        // The first argument is the size parameter for the circuit.
-        .render(9, &circuit, &root)
-        .unwrap();
+#[test]
+fn test_vmcircuit_operand_values() {
+    // the loop's jump targets are 93 (']') and 91 ('[')
+    let program = code::compile(format!("{}[-].", "+".repeat(90)).into_bytes());
+    mock_prove_circuit(program, vec![], 9);
+}
+
+#[test]
+fn test_vmcircuit_dropped_memory_row() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    // the last memory row satisfies the memory table gates on its own, only the
+    // permutation with the processor table notices that it is missing
+    vm.matrix.memory_matrix.pop();
+
+    let instances = prover::instances(&vm.matrix);
+
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_inconsistent_instruction() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    // an executed row that disagrees with the program row of the same ip
+    let row = vm
+        .matrix
+        .instruction_matrix
+        .windows(2)
+        .position(|w| w[0].instruction_pointer == w[1].instruction_pointer)
+        .unwrap()
+        + 1;
+    vm.matrix.instruction_matrix[row].current_instruction += Fr::one();
+    let instances = prover::instances(&vm.matrix);
+
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_one_hot() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    let instances = prover::instances(&vm.matrix);
+
+    let vmcircuit = VMCircuit::<Fr>::new(
+        vm.matrix.clone(),
+        BfCircuitParams {
+            one_hot: true,
+            ..Default::default()
+        },
+    );
+    let prover = MockProver::run(9, &vmcircuit, instances.clone()).unwrap();
+    prover.assert_satisfied();
+
+    let vmcircuit = VMCircuit::<Fr>::new(
+        vm.matrix,
+        BfCircuitParams {
+            io: IoMode::Evaluation,
+            one_hot: true,
+            ..Default::default()
+        },
+    );
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    prover.assert_satisfied();
+}
+
+#[test]
+fn test_vmcircuit_stark() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    let instances = prover::instances(&vm.matrix);
+
+    let vmcircuit = VMCircuit::<Fr>::new(
+        vm.matrix,
+        BfCircuitParams {
+            io: IoMode::Evaluation,
+            ..Default::default()
+        },
+    );
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    prover.assert_satisfied();
+}
+
+#[test]
+fn test_vmcircuit_stark_truncated_output() {
+    let program = code::compile(include_bytes!("../../res/hello_world.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.run();
+    // leaving the last output out of the public values is only caught by the
+    // running evaluation, the lookup accepts any subset of the outputs
+    vm.matrix.output_matrix.pop();
+    let instances = prover::instances(&vm.matrix);
+
+    let vmcircuit = VMCircuit::<Fr>::new(
+        vm.matrix,
+        BfCircuitParams {
+            io: IoMode::Evaluation,
+            ..Default::default()
+        },
+    );
+    let prover = MockProver::run(10, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_pasta() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    let instances = prover::instances(&vm.matrix);
+
+    let vmcircuit = VMCircuit::<Fp>::new(vm.matrix, BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    prover.assert_satisfied();
+}
+
+#[test]
+fn test_ipa_prove_verify() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    let instances = prover::instances(&vm.matrix);
+
+    let params = prover::ipa::setup(9);
+    let vmcircuit = VMCircuit::<Fp>::new(
+        vm.matrix,
+        BfCircuitParams {
+            io: IoMode::Evaluation,
+            ..Default::default()
+        },
+    );
+    let pk = prover::ipa::keygen(&params, &vmcircuit).unwrap();
+    let proof = prover::ipa::prove(&params, &pk, vmcircuit, &instances).unwrap();
+    prover::ipa::verify(&params, pk.get_vk(), &proof, &instances).unwrap();
+}
+
+#[test]
+fn test_vmcircuit_cell_bits() {
+    // the cell wraps around at 2^cell_bits in both directions
+    let program = code::compile(b"-.+.".to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_bits(4);
+    vm.set_code(program);
+    vm.run();
+    let instances = prover::instances(&vm.matrix);
+    assert_eq!(instances[0], vec![Fr::from(15), Fr::from(0)]);
+
+    let vmcircuit = VMCircuit::<Fr>::new(
+        vm.matrix,
+        BfCircuitParams {
+            cell_bits: 4,
+            ..Default::default()
+        },
+    );
+    let prover = MockProver::run(6, &vmcircuit, instances).unwrap();
+    prover.assert_satisfied();
+}
+
+#[test]
+fn test_vmcircuit_permutation_lookup() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    let instances = prover::instances(&vm.matrix);
+
+    // the memory lookup does not bind the public usage
+    let params = BfCircuitParams {
+        permutation: PermutationMode::Lookup,
+        ..Default::default()
+    };
+    assert!(params.check().is_err());
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, params);
+    assert!(MockProver::run(9, &vmcircuit, instances).is_err());
+}
+
+#[test]
+fn test_vmcircuit_max_cycles() {
+    let program = code::compile(include_bytes!("../../res/hello_world.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.run();
+    let cycles = vm.matrix.processor_matrix.len() - 1;
+    let instances = prover::instances(&vm.matrix);
+
+    let params = BfCircuitParams {
+        max_cycles: cycles,
+        ..Default::default()
+    };
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix.clone(), params);
+    assert!(MockProver::run(10, &vmcircuit, instances.clone()).is_ok());
+
+    let params = BfCircuitParams {
+        max_cycles: cycles - 1,
+        ..Default::default()
+    };
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, params);
+    assert!(MockProver::run(10, &vmcircuit, instances).is_err());
+}
+
+#[test]
+fn test_vmcircuit_eof() {
+    // cat, in the idiom of each EOF convention
+    for (eof, source) in [
+        (EofPolicy::Zero, &b",[.,]"[..]),
+        (EofPolicy::Max, &b",+[-.,+]"[..]),
+        (EofPolicy::Unchanged, &b",[.[-],]"[..]),
+    ] {
+        let mut vm = Interpreter::new();
+        vm.set_eof(eof);
+        vm.set_code(code::compile(source.to_vec()));
+        vm.set_input(code::easygen("cat"));
+        vm.run();
+        let instances = prover::instances(&vm.matrix);
+        assert_eq!(instances[0], instances[1]);
+
+        for io in [IoMode::Lookup, IoMode::Evaluation] {
+            let params = BfCircuitParams {
+                io,
+                eof,
+                ..Default::default()
+            };
+            let vmcircuit = VMCircuit::<Fr>::new(vm.matrix.clone(), params);
+            // only the input evaluation binds the eof flag, the lookup I/O
+            // mode keeps it at 0
+            if io == IoMode::Lookup {
+                if eof == EofPolicy::Zero {
+                    let prover = MockProver::run(9, &vmcircuit, instances.clone()).unwrap();
+                    assert!(prover.verify().is_err());
+                } else {
+                    assert!(params.check().is_err());
+                    assert!(MockProver::run(9, &vmcircuit, instances.clone()).is_err());
+                }
+                continue;
+            }
+            let prover = MockProver::run(9, &vmcircuit, instances.clone()).unwrap();
+            prover.assert_satisfied();
+        }
+    }
+}
+
+#[test]
+fn test_vmcircuit_eof_forged() {
+    // a run of cat that finds the input exhausted after "ca", proven against
+    // the public input "cat": the eof flag is raised one `,` early
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b",[.,]".to_vec()));
+    vm.set_input(code::easygen("ca"));
+    vm.run();
+    let mut instances = prover::instances(&vm.matrix);
+    assert_eq!(instances[0], code::easygen("ca"));
+    instances[1] = code::easygen("cat");
+
+    let params = BfCircuitParams {
+        io: IoMode::Evaluation,
+        ..Default::default()
+    };
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, params);
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_eof_policy_mismatch() {
+    // with Unchanged, `,` at EOF keeps the 1, which Zero does not allow
+    let mut vm = Interpreter::new();
+    vm.set_eof(EofPolicy::Unchanged);
+    vm.set_code(code::compile(b"+,.".to_vec()));
+    vm.run();
+    let instances = prover::instances(&vm.matrix);
+    assert_eq!(instances[0], vec![Fr::from(1)]);
+
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_strict_arithmetic() {
+    let trace = |source: &[u8]| {
+        let mut vm = Interpreter::new();
+        vm.set_bits(4);
+        vm.set_code(code::compile(source.to_vec()));
+        vm.run();
+        vm.matrix
+    };
+    let strict = BfCircuitParams {
+        cell_bits: 4,
+        arithmetic: Arithmetic::Strict,
+        ..Default::default()
+    };
+
+    // counting up to the largest value and back down never wraps
+    let matrix = trace(b"+++++++++++++++.[-].");
+    let instances = prover::instances(&matrix);
+    let vmcircuit = VMCircuit::<Fr>::new(matrix, strict);
+    let prover = MockProver::run(7, &vmcircuit, instances).unwrap();
+    prover.assert_satisfied();
+
+    // a trace that wraps does not satisfy the strict circuit
+    let matrix = trace(b"-.+.");
+    let instances = prover::instances(&matrix);
+    let vmcircuit = VMCircuit::<Fr>::new(matrix, strict);
+    let prover = MockProver::run(6, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_public_tape() {
+    // adds cell 1 into cell 0, cell 2 is never touched
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b">[-<+>]<.".to_vec()));
+    vm.set_tape(code::easygen("\x03\x04\x09"));
+    vm.run();
+    let mut instances = prover::instances(&vm.matrix);
+    instances.extend(prover::tape_instances(&vm.matrix));
+    assert_eq!(instances[3], code::easygen("\x03\x04"));
+    assert_eq!(instances[4], code::easygen("\x07\x00"));
+
+    for io in [IoMode::Lookup, IoMode::Evaluation] {
+        let params = BfCircuitParams {
+            io,
+            tape: TapeMode::Public,
+            ..Default::default()
+        };
+        let vmcircuit = VMCircuit::<Fr>::new(vm.matrix.clone(), params);
+        let prover = MockProver::run(9, &vmcircuit, instances.clone()).unwrap();
+        prover.assert_satisfied();
+    }
+
+    // claiming another final tape
+    let params = BfCircuitParams {
+        tape: TapeMode::Public,
+        ..Default::default()
+    };
+    let mut wrong = instances.clone();
+    wrong[4][1] = Fr::from(1);
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix.clone(), params);
+    let prover = MockProver::run(9, &vmcircuit, wrong).unwrap();
+    assert!(prover.verify().is_err());
+
+    // the memory lookup does not bind the final tape
+    let params = BfCircuitParams {
+        tape: TapeMode::Public,
+        permutation: PermutationMode::Lookup,
+        ..Default::default()
+    };
+    assert!(params.check().is_err());
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix.clone(), params);
+    assert!(MockProver::run(9, &vmcircuit, instances.clone()).is_err());
+
+    // without a public tape, cells have to start at zero
+    instances.truncate(3);
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_usage() {
+    let program = code::compile(include_bytes!("../../res/neptune_tutorial.bf").to_vec());
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.set_input(code::easygen("a"));
+    vm.run();
+    let usage = prover::Usage::of(&vm.matrix);
+    assert_eq!(usage.cycles, 18);
+    assert_eq!(usage.max_memory_pointer, 1);
+    let instances = prover::instances(&vm.matrix);
+    assert_eq!(instances[2], vec![Fr::from(18), Fr::from(1)]);
+
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+    // claiming fewer cycles or fewer cells
+    for (i, claim) in [(0, 17), (1, 0)] {
+        let mut wrong = instances.clone();
+        wrong[2][i] = Fr::from(claim);
+        let prover = MockProver::run(9, &vmcircuit, wrong).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
+
+#[test]
+fn test_vmcircuit_usage_permutation_lookup() {
+    // a memory table without the rows of cells 1 and 2, claiming that the
+    // program only touched cell 0
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b"+>+>+".to_vec()));
+    vm.run();
+    let mut instances = prover::instances(&vm.matrix);
+    assert_eq!(instances[2], vec![Fr::from(5), Fr::from(2)]);
+    vm.matrix
+        .memory_matrix
+        .retain(|r| r.memory_pointer == Fr::from(0));
+    instances[2][1] = Fr::from(0);
+
+    // only the running product binds the highest memory pointer
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix.clone(), BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances.clone()).unwrap();
+    assert!(prover.verify().is_err());
+
+    let params = BfCircuitParams {
+        permutation: PermutationMode::Lookup,
+        ..Default::default()
+    };
+    assert!(params.check().is_err());
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, params);
+    assert!(MockProver::run(9, &vmcircuit, instances).is_err());
+}
+
+#[test]
+fn test_vmcircuit_usage_truncated() {
+    // the first 4 cycles of `+.+.+.`, claiming a run of 3 cycles
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b"+.+.+.".to_vec()));
+    vm.run();
+    let mut matrix = vm.matrix;
+    matrix.processor_matrix.truncate(4);
+    let cycles: Vec<Fr> = matrix.processor_matrix.iter().map(|r| r.cycle).collect();
+    matrix.memory_matrix.retain(|r| cycles.contains(&r.cycle));
+    matrix.output_matrix.retain(|r| cycles.contains(&r.cycle));
+    let instances = prover::instances(&matrix);
+    assert_eq!(instances[0], vec![Fr::from(1), Fr::from(2)]);
+    assert_eq!(instances[2], vec![Fr::from(3), Fr::from(0)]);
+
+    // the last processor row has to be the halted state
+    let vmcircuit = VMCircuit::<Fr>::new(matrix, BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_left_of_origin() {
+    // a hand-built trace of `<`, moving to cell -1
+    let program = code::compile(b"<".to_vec());
+    let rows = program_rows(&program);
+    let start = Register {
+        current_instruction: rows[0].current_instruction,
+        ..Default::default()
+    };
+    let halted = Register {
+        cycle: Fr::from(1),
+        instruction_pointer: Fr::from(1),
+        memory_pointer: -Fr::from(1),
+        ..Default::default()
+    };
+    let matrix = Matrix {
+        instruction_matrix: vec![
+            rows[0].clone(),
+            InstructionMatrixRow::from(&start),
+            InstructionMatrixRow::from(&halted),
+        ],
+        // sorted by mp, the table would start at p - 1
+        memory_matrix: vec![
+            MemoryMatrixRow::from(&halted),
+            MemoryMatrixRow::from(&start),
+        ],
+        processor_matrix: vec![start, halted],
+        program: rows,
+        ..Default::default()
+    };
+    let instances = prover::instances(&matrix);
+    assert_eq!(instances[2], vec![Fr::from(1), Fr::from(0)]);
+
+    let vmcircuit = VMCircuit::<Fr>::new(matrix, BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_instruction_past_end() {
+    // the trace of `+.` proven against the program `+`: the instruction table
+    // runs a `.` appended past the end of the program and outputs 1
+    let mut vm = Interpreter::new();
+    vm.set_code(code::compile(b"+.".to_vec()));
+    vm.run();
+    let mut matrix = vm.matrix;
+    matrix.program = program_rows(&code::compile(b"+".to_vec()));
+    for row in matrix.instruction_matrix.iter_mut() {
+        if row.instruction_pointer == Fr::from(0) {
+            row.next_instruction = Fr::from(0);
+        }
+    }
+    for row in matrix.processor_matrix.iter_mut() {
+        if row.instruction_pointer == Fr::from(0) {
+            row.next_instruction = Fr::from(0);
+        }
+    }
+    let instances = prover::instances(&matrix);
+    assert_eq!(instances[0], vec![Fr::from(1)]);
+
+    let vmcircuit = VMCircuit::<Fr>::new(matrix, BfCircuitParams::default());
+    let prover = MockProver::run(9, &vmcircuit, instances).unwrap();
+    assert!(prover.verify().is_err());
+}
+
+#[test]
+fn test_vmcircuit_large_operand() {
+    // a skipped loop over 70000 instructions: `[` jumps to 70002, past 16 bits
+    let mut source = "[".to_string();
+    source.push_str(&">".repeat(70_000));
+    source.push_str("].");
+    let program = code::try_compile(source.into_bytes()).unwrap();
+    assert_eq!(program[0], code::Instruction::new(b'[', 70_002));
+
+    let mut vm = Interpreter::new();
+    vm.set_code(program);
+    vm.run();
+    assert_eq!(vm.matrix.processor_matrix.len(), 3);
+    let instances = prover::instances(&vm.matrix);
+
+    let vmcircuit = VMCircuit::<Fr>::new(vm.matrix, BfCircuitParams::default());
+    let prover = MockProver::run(17, &vmcircuit, instances).unwrap();
+    prover.assert_satisfied();
 }